@@ -1,7 +1,15 @@
 mod hls_source;
 mod mp3;
 
-use std::{collections::VecDeque, error::Error, sync::Arc, thread, time};
+use std::{
+    collections::{HashSet, VecDeque},
+    error::Error,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread, time,
+};
 
 use async_trait::async_trait;
 use eyre::Result;
@@ -42,12 +50,33 @@ pub trait Downloader: Send + Sync {
     async fn media_playlist(&self, id: SongId) -> Result<MediaPlaylist> {
         let playlist = self.playlist(id).await?;
         let bytes = playlist.as_bytes().to_vec();
-        Ok(m3u8_rs::parse_media_playlist(&bytes).unwrap().1)
+        m3u8_rs::parse_media_playlist(&bytes)
+            .map(|(_, playlist)| playlist)
+            .map_err(|err| eyre::eyre!("Failed to parse media playlist: {:?}", err))
+    }
+
+    /// Songs to carry on with after ids when autoplaying, best first
+    async fn related(&self, _ids: &[SongId]) -> Result<Vec<SongId>> {
+        Ok(vec![])
     }
 }
 
 pub type SongId = i64;
 
+/// How many times in a row a segment can fail (refreshing the playlist in between)
+/// before we give up on the track
+const MAX_SEGMENT_FAILURES: u32 = 5;
+const SEGMENT_RETRY_INTERVAL: time::Duration = time::Duration::from_millis(500);
+
+/// Start looking for more songs once there are this few left in the queue
+const AUTOPLAY_THRESHOLD: usize = 2;
+/// How many of the most recently played songs to look for related songs of
+const AUTOPLAY_SEEDS: usize = 3;
+/// How many songs to add to the queue at once
+const AUTOPLAY_BATCH: usize = 10;
+/// How many played songs to remember, so that autoplay does not repeat them
+const HISTORY_LEN: usize = 500;
+
 #[derive(Debug)]
 enum PlayerControl {
     Pause,
@@ -55,22 +84,32 @@ enum PlayerControl {
     SkipAll,
     SkipOne,
     Volume(f32),
-    Seek(usize),
+    /// Seconds into the current song
+    Seek(f32),
     Queue(SongId),
     QueueMany(Vec<SongId>),
+    ReplaceQueue(Vec<SongId>),
+    PlayNow(SongId),
     Looping(Looping),
+    Autoplay(bool),
+    /// Related songs came back for autoplay
+    AutoplayFound(Vec<SongId>),
 }
 
 #[derive(Debug, Clone)]
 pub struct PlayerState {
     pub playing: Playing,
     pub looping: Looping,
+    /// Keep playing related songs when the queue runs out
+    pub autoplay: bool,
 
     pub sample_rate: usize,
     /// Number of samples into the track
     pub pos: usize,
     /// total time in seconds
     pub total: f32,
+    /// How far into the track (in seconds) has been downloaded
+    pub buffered: f32,
     /// Index into the queue
     pub queue_pos_index: Option<usize>,
     /// Current Song
@@ -82,9 +121,11 @@ impl Default for PlayerState {
         Self {
             playing: Default::default(),
             looping: Default::default(),
+            autoplay: false,
             sample_rate: 44100,
             pos: Default::default(),
             total: Default::default(),
+            buffered: Default::default(),
             queue_pos_index: None,
             cur_song: None,
         }
@@ -147,6 +188,16 @@ impl HlsPlayer {
         Ok(self.control.send(PlayerControl::QueueMany(ids)).await?)
     }
 
+    /// Insert id after the current song and start playing it straight away
+    pub async fn play_now(&self, id: SongId) -> Result<()> {
+        Ok(self.control.send(PlayerControl::PlayNow(id)).await?)
+    }
+
+    /// Throw away the current queue and start playing ids from the beginning
+    pub async fn replace_queue(&self, ids: Vec<SongId>) -> Result<()> {
+        Ok(self.control.send(PlayerControl::ReplaceQueue(ids)).await?)
+    }
+
     pub async fn resume(&self) -> Result<()> {
         info!("Resuming playback");
         Ok(self.control.send(PlayerControl::Resume).await?)
@@ -180,6 +231,11 @@ impl HlsPlayer {
         self.cur_song.clone()
     }
 
+    /// Jump to secs into the current song
+    pub async fn seek(&self, secs: f32) -> Result<()> {
+        Ok(self.control.send(PlayerControl::Seek(secs)).await?)
+    }
+
     pub async fn volume(&self, volume: f32) -> Result<()> {
         Ok(self.control.send(PlayerControl::Volume(volume)).await?)
     }
@@ -187,6 +243,11 @@ impl HlsPlayer {
     pub async fn looping(&self, looping: Looping) -> Result<()> {
         Ok(self.control.send(PlayerControl::Looping(looping)).await?)
     }
+
+    /// Whether to queue up related songs when the queue is about to run out
+    pub async fn autoplay(&self, autoplay: bool) -> Result<()> {
+        Ok(self.control.send(PlayerControl::Autoplay(autoplay)).await?)
+    }
 }
 
 struct SinkStream {
@@ -234,11 +295,22 @@ struct Inner {
     finished_signal_tx: mpsc::Sender<()>,
     finished_signal_rx: Option<mpsc::Receiver<()>>,
 
-    sink_stream: Mutex<SinkStream>,
+    /// Only opened once something plays
+    sink_stream: Mutex<Option<SinkStream>>,
     downloader: Arc<dyn Downloader>,
     queue: VecDeque<SongId>,
     queue_pos_index: Option<usize>,
     looping: Looping,
+    /// Playlist of the song that is playing, kept around for seeking
+    cur_playlist: Option<MediaPlaylist>,
+    /// Bumped every time playback (re)starts, so that downloads for anything before
+    /// know to stop reporting progress
+    playback_generation: Arc<AtomicU64>,
+    autoplay: bool,
+    /// Whether we are already looking for songs to autoplay
+    autoplay_pending: bool,
+    /// Songs that have been played, most recent last
+    history: VecDeque<SongId>,
 }
 
 impl Inner {
@@ -262,11 +334,16 @@ impl Inner {
             cur_song_rx,
             queued_song_tx,
             queue: VecDeque::new(),
-            sink_stream: Mutex::new(SinkStream::new()),
+            sink_stream: Mutex::new(None),
             finished_signal_tx,
             finished_signal_rx: Some(finished_signal_rx),
             queue_pos_index: None,
             looping: Looping::default(),
+            cur_playlist: None,
+            playback_generation: Default::default(),
+            autoplay: false,
+            autoplay_pending: false,
+            history: VecDeque::new(),
         }
     }
 
@@ -287,11 +364,17 @@ impl Inner {
     }
 
     async fn sink(&self) -> MappedMutexGuard<rodio::Sink> {
-        tokio::sync::MutexGuard::map(self.sink_stream.lock().await, |s| &mut s.sink)
+        tokio::sync::MutexGuard::map(self.sink_stream.lock().await, |s| {
+            &mut s.get_or_insert_with(SinkStream::new).sink
+        })
     }
 
     async fn reset_sink(&self) {
-        self.sink_stream.lock().await.reset();
+        let mut sink_stream = self.sink_stream.lock().await;
+        match sink_stream.as_mut() {
+            Some(sink_stream) => sink_stream.reset(),
+            None => *sink_stream = Some(SinkStream::new()),
+        }
     }
 
     async fn handle_control(
@@ -338,15 +421,137 @@ impl Inner {
                     });
                 }
             }
-            PlayerControl::Volume(volume) => self.sink_stream.lock().await.set_volume(volume),
-            PlayerControl::Seek(_) => todo!(),
+            PlayerControl::ReplaceQueue(ids) => {
+                info!("Replacing queue");
+                self.queue = ids.into();
+                self.queue_pos_index = None;
+                self.queued_song_tx.send(self.queue.clone()).unwrap();
+                self.skip_one(finished_signal_rx).await;
+            }
+            PlayerControl::PlayNow(id) => {
+                info!("Playing track now");
+                let index = self
+                    .queue_pos_index
+                    .map_or(self.queue.len(), |index| index + 1);
+                self.queue.insert(index, id);
+                self.queue_pos_index = Some(index);
+                self.queued_song_tx.send(self.queue.clone()).unwrap();
+                self.play_index(Some(index), finished_signal_rx).await;
+            }
+            PlayerControl::Volume(volume) => self
+                .sink_stream
+                .lock()
+                .await
+                .get_or_insert_with(SinkStream::new)
+                .set_volume(volume),
+            PlayerControl::Seek(secs) => self.seek(secs, finished_signal_rx).await,
             PlayerControl::Looping(looping) => {
                 self.looping = looping;
                 self.state_tx.send_modify(|state| state.looping = looping);
+                // NOTE(emily): Turning looping off might leave us near the end of the queue
+                self.maybe_autoplay();
+            }
+            PlayerControl::Autoplay(autoplay) => {
+                self.autoplay = autoplay;
+                self.state_tx.send_modify(|state| state.autoplay = autoplay);
+                self.maybe_autoplay();
+            }
+            PlayerControl::AutoplayFound(ids) => {
+                self.autoplay_found(ids, finished_signal_rx).await;
             }
         }
     }
 
+    /// If the queue is about to run out, start looking for related songs to add to it
+    fn maybe_autoplay(&mut self) {
+        // NOTE(emily): Looping means the queue never runs out
+        if !self.autoplay || self.autoplay_pending || !matches!(self.looping, Looping::None) {
+            return;
+        }
+
+        let remaining = match self.queue_pos_index {
+            Some(index) => self.queue.len() - index - 1,
+            None => 0,
+        };
+        if remaining >= AUTOPLAY_THRESHOLD {
+            return;
+        }
+
+        let seeds: Vec<SongId> = self
+            .history
+            .iter()
+            .rev()
+            .take(AUTOPLAY_SEEDS)
+            .copied()
+            .collect();
+        if seeds.is_empty() {
+            return;
+        }
+
+        info!("Looking for songs to autoplay after {:?}", seeds);
+        self.autoplay_pending = true;
+
+        let downloader = self.downloader.clone();
+        let loop_control_tx = self.loop_control_tx.clone();
+        tokio::spawn(async move {
+            let ids = downloader.related(&seeds).await.unwrap_or_else(|err| {
+                warn!("Failed to find songs to autoplay ({:?})", err);
+                vec![]
+            });
+            loop_control_tx
+                .send(PlayerControl::AutoplayFound(ids))
+                .await
+                .unwrap();
+        });
+    }
+
+    async fn autoplay_found(
+        &mut self,
+        ids: Vec<SongId>,
+        finished_signal_rx: &mut mpsc::Receiver<()>,
+    ) {
+        self.autoplay_pending = false;
+
+        // NOTE(emily): If the queue already ran out then pick up where it left off,
+        // rather than going back to the start of the queue
+        if let Some(start) = self.queue_autoplay(ids) {
+            self.play_index(Some(start), finished_signal_rx).await;
+        }
+    }
+
+    /// Add the songs in ids that have not been played or queued yet to the end of the queue,
+    /// returning where to start playing from if the queue already ran out
+    fn queue_autoplay(&mut self, ids: Vec<SongId>) -> Option<usize> {
+        if !self.autoplay {
+            return None;
+        }
+
+        let mut seen: HashSet<SongId> = self
+            .history
+            .iter()
+            .chain(self.queue.iter())
+            .copied()
+            .collect();
+        let ids: Vec<SongId> = ids
+            .into_iter()
+            .filter(|id| seen.insert(*id))
+            .take(AUTOPLAY_BATCH)
+            .collect();
+
+        if ids.is_empty() {
+            info!("Nothing new to autoplay");
+            return None;
+        }
+
+        info!("Autoplaying {} more songs", ids.len());
+        let start = self.queue.len();
+        self.queue.extend(ids.iter());
+        self.queued_song_tx.send(self.queue.clone()).unwrap();
+
+        // NOTE(emily): There is only no position in the queue when nothing is playing
+        self.queue_pos_index.is_none().then_some(start)
+    }
+
     async fn next_track(&mut self) -> Option<usize> {
         // First, if we are not currently queuing anything
         // then just try index 0
@@ -390,87 +595,225 @@ impl Inner {
     }
 
     async fn skip_one(&mut self, finished_signal_rx: &mut mpsc::Receiver<()>) {
-        match self.next_track().await {
-            Some(index) => {
-                let queued_song = self.queue[index];
-
-                // Ask for the playlist AOT
-                let playlist = self.downloader.media_playlist(queued_song).await.unwrap();
-
-                // Calculate the total length of the track
-                let total = playlist.segments.iter().map(|x| x.duration).sum::<f32>();
+        let index = self.next_track().await;
+        self.play_index(index, finished_signal_rx).await;
+    }
 
+    /// Start playing the song at index in the queue, or stop if there is no index
+    async fn play_index(
+        &mut self,
+        index: Option<usize>,
+        finished_signal_rx: &mut mpsc::Receiver<()>,
+    ) {
+        // Ask for the playlist AOT, skipping over anything that we cant play
+        match self.first_playable(index).await {
+            Some((index, playlist)) => {
                 // Reset sink
                 self.reset_sink().await;
-                // If we got a finished signal then consume it
-                finished_signal_rx
-                    .try_recv()
-                    .map(|_| info!("Consumed a finished signal"))
-                    .unwrap_or_else(|_| {
-                        info!("No finished signal to consume");
-                        ()
-                    });
+                Self::consume_finished_signal(finished_signal_rx);
 
-                self.state_tx.send_modify(|state| {
-                    state.queue_pos_index = Some(index);
-                    state.cur_song = Some(queued_song);
-                });
+                self.now_playing(index);
 
-                // Tell everyone that we are playing a new track
-                self.cur_song_tx.send(Some(queued_song)).unwrap();
-
-                let chunk_rx = self.download_hls_segments(playlist).await;
-
-                match HlsDecoder::new(chunk_rx, &self.finished_signal_tx).await {
-                    Ok(source) => {
-                        let state_tx = self.state_tx.clone();
-                        let source = source.periodic_access(
-                            time::Duration::from_millis(100),
-                            move |source| {
-                                state_tx.send_modify(|state| {
-                                    state.playing = Playing::Playing;
-                                    (state.sample_rate, state.pos, state.total) =
-                                        (source.sample_rate() as usize, source.samples(), total);
-                                });
-                            },
-                        );
-
-                        let sink = self.sink().await;
-                        sink.append(source);
-                        sink.play();
-                    }
-                    Err(err) => {
-                        warn!("Failed to get first chunks of HlsDecoder {:?}", err);
-                        self.loop_control_tx
-                            .send(PlayerControl::SkipOne)
-                            .await
-                            .unwrap();
-                    }
-                }
+                self.start_playback(playlist, 0, 0.0, true).await;
+                self.maybe_autoplay();
             }
             None => {
                 // Nothing in queue so reset sink and inform everyone
                 self.reset_sink().await;
 
+                self.cur_playlist = None;
                 self.state_tx.send_modify(|state| {
                     state.queue_pos_index = None;
                     state.cur_song = None;
                 });
 
                 self.cur_song_tx.send(None).unwrap();
+
+                // NOTE(emily): Usually we will have found more songs well before now,
+                // but autoplay might have only just been turned on
+                self.maybe_autoplay();
+            }
+        }
+    }
+
+    /// Make the song at index in the queue the current song
+    fn now_playing(&mut self, index: usize) {
+        let queued_song = self.queue[index];
+        // NOTE(emily): Looping one song plays it again without the song changing
+        let changed = self.state_tx.borrow().cur_song != Some(queued_song);
+
+        self.queue_pos_index = Some(index);
+        self.state_tx.send_modify(|state| {
+            state.queue_pos_index = Some(index);
+            state.cur_song = Some(queued_song);
+        });
+
+        // Tell everyone that we are playing a new track
+        self.cur_song_tx.send(Some(queued_song)).unwrap();
+
+        if changed {
+            self.history.push_back(queued_song);
+            if self.history.len() > HISTORY_LEN {
+                self.history.pop_front();
             }
         }
     }
 
+    fn consume_finished_signal(finished_signal_rx: &mut mpsc::Receiver<()>) {
+        // If we got a finished signal then consume it
+        finished_signal_rx
+            .try_recv()
+            .map(|_| info!("Consumed a finished signal"))
+            .unwrap_or_else(|_| {
+                info!("No finished signal to consume");
+                ()
+            });
+    }
+
+    /// Start playing the current song from skip seconds into segment start of playlist
+    async fn start_playback(
+        &mut self,
+        playlist: MediaPlaylist,
+        start: usize,
+        skip: f32,
+        play: bool,
+    ) {
+        // Calculate the total length of the track
+        let total = playlist.segments.iter().map(|x| x.duration).sum::<f32>();
+        // And where in the track the first segment that we play starts
+        let offset = playlist.segments[..start]
+            .iter()
+            .map(|x| x.duration)
+            .sum::<f32>();
+
+        let generation = self.playback_generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.cur_playlist = Some(playlist.clone());
+        self.state_tx.send_modify(|state| state.buffered = offset);
+
+        let chunk_rx = self
+            .download_hls_segments(playlist, start, generation)
+            .await;
+
+        match HlsDecoder::new(chunk_rx, &self.finished_signal_tx, skip).await {
+            Ok(source) => {
+                let state_tx = self.state_tx.clone();
+                let source =
+                    source.periodic_access(time::Duration::from_millis(100), move |source| {
+                        // NOTE(emily): pos counts interleaved samples, so every channel counts
+                        let offset_samples =
+                            (offset * source.sample_rate() as f32 * source.channels() as f32)
+                                as usize;
+                        state_tx.send_modify(|state| {
+                            state.playing = Playing::Playing;
+                            (state.sample_rate, state.pos, state.total) = (
+                                source.sample_rate() as usize,
+                                offset_samples + source.samples(),
+                                total,
+                            );
+                        });
+                    });
+
+                let sink = self.sink().await;
+                sink.append(source);
+                if play {
+                    sink.play();
+                } else {
+                    sink.pause();
+                }
+            }
+            Err(err) => {
+                warn!("Failed to get first chunks of HlsDecoder {:?}", err);
+                self.loop_control_tx
+                    .send(PlayerControl::SkipOne)
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+
+    async fn seek(&mut self, secs: f32, finished_signal_rx: &mut mpsc::Receiver<()>) {
+        let Some(playlist) = self.cur_playlist.clone() else {
+            return;
+        };
+
+        // Start from the segment that secs is in
+        let start = playlist
+            .segments
+            .iter()
+            .scan(0.0, |end, segment| {
+                *end += segment.duration;
+                Some(*end)
+            })
+            .position(|end| end > secs)
+            .unwrap_or(playlist.segments.len().saturating_sub(1));
+
+        // And skip whatever is before secs in that segment
+        let offset = playlist.segments[..start]
+            .iter()
+            .map(|x| x.duration)
+            .sum::<f32>();
+        let skip = (secs - offset).max(0.0);
+
+        info!("Seeking to {secs}s (segment {start} + {skip}s)");
+
+        let playing = matches!(self.state_tx.borrow().playing, Playing::Playing);
+
+        self.reset_sink().await;
+        Self::consume_finished_signal(finished_signal_rx);
+
+        self.start_playback(playlist, start, skip, playing).await;
+    }
+
+    /// The index that comes after index when moving past a song that could not be played
+    fn index_after(&self, index: usize) -> Option<usize> {
+        if index + 1 < self.queue.len() {
+            Some(index + 1)
+        } else if let Looping::Loop = self.looping {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// Find the first song, starting at index, that we can get a playlist for
+    async fn first_playable(&mut self, mut index: Option<usize>) -> Option<(usize, MediaPlaylist)> {
+        let mut failures = 0;
+
+        while let Some(i) = index {
+            let id = self.queue[i];
+            match self.downloader.media_playlist(id).await {
+                Ok(playlist) => return Some((i, playlist)),
+                Err(err) => {
+                    warn!("Skipping song {id} that can not be played ({:?})", err);
+                    failures += 1;
+                    // NOTE(emily): Dont go round forever if nothing in the queue is playable
+                    index = if failures < self.queue.len() {
+                        self.index_after(i)
+                    } else {
+                        None
+                    };
+                    self.queue_pos_index = index;
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Download the segments of playlist from start onwards, in the background
     async fn download_hls_segments(
         &mut self,
         mut playlist: MediaPlaylist,
+        start: usize,
+        generation: u64,
         // downloader: Arc<dyn Downloader>,
         // mut cur_song_rx: watch::Receiver<Option<SongId>>,
     ) -> mpsc::Receiver<Vec<u8>> {
         // Acknowledge current track id
-        self.cur_song_rx.changed().await.unwrap();
-        let id = self.cur_song_rx.borrow().clone().unwrap();
+        // NOTE(emily): When seeking the track has not changed, so dont wait for it to
+        let id = self.cur_song_rx.borrow_and_update().clone().unwrap();
+        let state_tx = self.state_tx.clone();
+        let playback_generation = self.playback_generation.clone();
 
         // Buffer bound here is how many chunks ahead we download before waiting for them
         // to get played. On average a chunk is ~1 second.
@@ -478,13 +821,24 @@ impl Inner {
         let downloader = self.downloader.clone();
 
         tokio::spawn(async move {
-            let mut i = 0;
+            let mut i = start;
+            // How many times in a row we have failed to get segment i
+            let mut failures = 0;
             while i < playlist.segments.len() {
                 match downloader.download_chunk(&playlist.segments[i].uri).await {
                     Ok(chunk) => {
                         // We successfully got the ith chunk, lets keep going
                         info!("downloaded {i}");
+                        let end = playlist.segments[..=i]
+                            .iter()
+                            .map(|x| x.duration)
+                            .sum::<f32>();
+                        if playback_generation.load(Ordering::SeqCst) == generation {
+                            state_tx.send_modify(|state| state.buffered = end);
+                        }
+
                         i += 1;
+                        failures = 0;
 
                         match tx_chunk.send(chunk).await {
                             Ok(_) => {}
@@ -496,14 +850,25 @@ impl Inner {
                     }
                     Err(err) => {
                         warn!("Failed to download HLS Segment {} {:?}", i, err);
+                        failures += 1;
+                        if failures > MAX_SEGMENT_FAILURES {
+                            warn!("Segment {i} failed too many times... No longer downloading");
+                            return;
+                        }
+
+                        // Back off a little before trying again
+                        tokio::time::sleep(SEGMENT_RETRY_INTERVAL * 2u32.pow(failures - 1)).await;
+
                         // NOTE(emily): The playlist we were downloading might have just expired
                         // So we are going to try and get the playlist again...
-                        if let Ok(new_playlist) = downloader.media_playlist(id).await {
-                            info!("Successfully updated playlist");
-                            playlist = new_playlist;
-                        } else {
-                            warn!("Failed to re-download playlist... No longer downloading");
-                            return;
+                        match downloader.media_playlist(id).await {
+                            Ok(new_playlist) => {
+                                info!("Successfully updated playlist");
+                                playlist = new_playlist;
+                            }
+                            Err(err) => {
+                                warn!("Failed to re-download playlist ({:?})", err);
+                            }
                         }
                     }
                 }
@@ -514,3 +879,100 @@ impl Inner {
         return rx_chunk;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoDownloader;
+
+    #[async_trait]
+    impl Downloader for NoDownloader {
+        async fn download_chunk(&self, _url: &str) -> Result<Vec<u8>> {
+            Err(eyre::eyre!("Nothing to download"))
+        }
+
+        async fn playlist(&self, _id: SongId) -> Result<String> {
+            Err(eyre::eyre!("Nothing to download"))
+        }
+    }
+
+    /// An Inner with queue in it, along with the queue watch to keep it open
+    fn inner(queue: Vec<SongId>) -> (Inner, watch::Receiver<VecDeque<SongId>>) {
+        let (control_tx, control_rx) = mpsc::channel(10);
+        let (state_tx, _) = watch::channel(PlayerState::default());
+        let (cur_song_tx, cur_song_rx) = watch::channel(None);
+        let (queued_song_tx, queued_song_rx) = watch::channel(VecDeque::new());
+
+        let mut inner = Inner::new(
+            control_rx,
+            Arc::new(NoDownloader),
+            Arc::new(state_tx),
+            control_tx,
+            cur_song_tx,
+            cur_song_rx,
+            queued_song_tx,
+        );
+        inner.queue = queue.into();
+
+        (inner, queued_song_rx)
+    }
+
+    /// Finish the current song the way the finished signal would
+    async fn finish(inner: &mut Inner) -> Option<usize> {
+        let index = inner.next_track().await;
+        if let Some(index) = index {
+            inner.now_playing(index);
+        }
+        index
+    }
+
+    #[tokio::test]
+    async fn autoplay_carries_on_after_the_queue_runs_out() {
+        let (mut inner, _queued) = inner(vec![1, 2]);
+        inner.looping = Looping::None;
+        inner.autoplay = true;
+
+        assert_eq!(finish(&mut inner).await, Some(0));
+        assert_eq!(finish(&mut inner).await, Some(1));
+        assert_eq!(finish(&mut inner).await, None);
+
+        // 1 has already been played so it doesnt get queued again
+        let start = inner.queue_autoplay(vec![1, 3, 4]);
+        assert_eq!(start, Some(2));
+        assert_eq!(inner.queue, [1, 2, 3, 4]);
+
+        inner.now_playing(2);
+        assert_eq!(inner.queue_pos_index, Some(2));
+
+        // Then the next autoplayed song plays rather than the start of the queue
+        assert_eq!(finish(&mut inner).await, Some(3));
+        assert_eq!(inner.history, [1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn autoplay_doesnt_restart_a_queue_that_is_still_playing() {
+        let (mut inner, _queued) = inner(vec![1, 2]);
+        inner.looping = Looping::None;
+        inner.autoplay = true;
+
+        assert_eq!(finish(&mut inner).await, Some(0));
+        assert_eq!(inner.queue_autoplay(vec![3]), None);
+        assert_eq!(inner.queue, [1, 2, 3]);
+        assert_eq!(finish(&mut inner).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn looping_one_song_only_remembers_it_once() {
+        let (mut inner, _queued) = inner(vec![1, 2]);
+
+        assert_eq!(finish(&mut inner).await, Some(0));
+        assert_eq!(finish(&mut inner).await, Some(0));
+        assert_eq!(finish(&mut inner).await, Some(0));
+        assert_eq!(inner.history, [1]);
+
+        inner.looping = Looping::None;
+        assert_eq!(finish(&mut inner).await, Some(1));
+        assert_eq!(inner.history, [1, 2]);
+    }
+}
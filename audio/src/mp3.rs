@@ -1,6 +1,6 @@
 use std::{error::Error, time};
 
-use eyre::Result;
+use eyre::{eyre, Result};
 use log::info;
 use minimp3::{Decoder, Frame};
 use rodio::Source;
@@ -17,9 +17,11 @@ pub struct HlsDecoder {
 }
 
 impl HlsDecoder {
+    /// Decode the mp3 arriving on chunk_rx, starting skip seconds in
     pub async fn new(
         chunk_rx: mpsc::Receiver<Vec<u8>>,
         finished_signal: &tokio::sync::mpsc::Sender<()>,
+        skip: f32,
     ) -> Result<Self> {
         let (next_frame_tx, mut next_frame_rx) = mpsc::channel(30);
 
@@ -43,12 +45,27 @@ impl HlsDecoder {
         });
 
         // Make sure that we have a frame ready to go
-        let current_frame = next_frame_rx.recv().await.unwrap();
+        let mut current_frame = next_frame_rx.recv().await.unwrap();
+
+        // NOTE(emily): Skipped samples still count towards samples(), so that the position
+        // carries on from where we skipped to
+        let mut elapsed = 0;
+        let mut to_skip = (skip.max(0.0)
+            * current_frame.sample_rate as f32
+            * current_frame.channels as f32) as usize;
+        while to_skip >= current_frame.data.len() {
+            elapsed += current_frame.data.len();
+            to_skip -= current_frame.data.len();
+            current_frame = next_frame_rx
+                .recv()
+                .await
+                .ok_or_else(|| eyre!("Ran out of frames skipping {skip}s"))?;
+        }
 
         Ok(HlsDecoder {
             current_frame,
-            current_frame_offset: 0,
-            elapsed: 0,
+            current_frame_offset: to_skip,
+            elapsed,
             finished_signal: finished_signal.clone(),
             next_frame_rx,
         })
@@ -1,10 +1,84 @@
-use eyre::{eyre, Result};
+use eyre::{Report, Result};
 use futures::Future;
-use std::{cmp, collections::HashMap, fmt::Debug, sync::Arc};
+use std::{
+    cmp,
+    collections::HashMap,
+    fmt::{self, Debug},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tokio::sync::{Mutex, RwLock};
+// NOTE(emily): tokio's Instant so that tests can pause and advance the clock
+use tokio::time::Instant;
+
+/// How long a failed lookup is remembered before we try again
+const DEFAULT_ERROR_TTL: Duration = Duration::from_secs(30);
+
+/// An error that was stored in the cache. Every caller waiting on the same lookup gets one of
+/// these, and the original error is still available through source() so that it can be
+/// downcast (e.g. with Report::chain).
+#[derive(Debug, Clone)]
+pub struct CachedError(Arc<Report>);
+
+impl fmt::Display for CachedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for CachedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        let report: &Report = &self.0;
+        Some(AsRef::<dyn std::error::Error>::as_ref(report))
+    }
+}
+
+struct Stored<V> {
+    value: std::result::Result<Arc<V>, CachedError>,
+    stored_at: Instant,
+}
+
+struct Slot<V> {
+    stored: Mutex<Option<Stored<V>>>,
+    /// Value of Cache::clock when this slot was last touched. Used for LRU eviction.
+    last_used: AtomicU64,
+}
+
+impl<V> Slot<V> {
+    fn new(tick: u64) -> Self {
+        Self {
+            stored: Mutex::new(None),
+            last_used: AtomicU64::new(tick),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CacheMetrics {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub errors: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    errors: AtomicU64,
+}
 
 pub struct Cache<K, V> {
-    values: RwLock<HashMap<K, Arc<Mutex<Option<Result<Arc<V>>>>>>>,
+    values: RwLock<HashMap<K, Arc<Slot<V>>>>,
+    capacity: Option<usize>,
+    ttl: Option<Duration>,
+    error_ttl: Duration,
+    clock: AtomicU64,
+    counters: Counters,
 }
 
 impl<K, V> Default for Cache<K, V>
@@ -23,47 +97,322 @@ where
     pub fn new() -> Self {
         Self {
             values: Default::default(),
+            capacity: None,
+            ttl: None,
+            error_ttl: DEFAULT_ERROR_TTL,
+            clock: AtomicU64::new(0),
+            counters: Default::default(),
         }
     }
 
-    pub async fn get<F>(&self, key: &K, f: F) -> Result<Arc<V>>
-    where
-        F: Future<Output = Result<Arc<V>>> + Send,
-    {
+    /// Bound the cache to `capacity` entries, evicting the least recently used
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Successful values older than `ttl` are fetched again on next access
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Errors older than `error_ttl` are retried on next access
+    pub fn with_error_ttl(mut self, error_ttl: Duration) -> Self {
+        self.error_ttl = error_ttl;
+        self
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn is_fresh(&self, stored: &Stored<V>) -> bool {
+        let ttl = match &stored.value {
+            Ok(_) => self.ttl,
+            Err(_) => Some(self.error_ttl),
+        };
+
+        ttl.map_or(true, |ttl| stored.stored_at.elapsed() < ttl)
+    }
+
+    fn cached_result(stored: &Stored<V>) -> Result<Arc<V>> {
+        stored.value.clone().map_err(Report::new)
+    }
+
+    /// Get the slot for key, creating it (and possibly evicting another) if it is missing
+    async fn slot(&self, key: &K) -> Arc<Slot<V>> {
+        let tick = self.tick();
+
         {
             let values = self.values.read().await;
-            if let Some(v) = values.get(key) {
-                let v = v.lock().await;
-                let r = v.as_ref().unwrap();
-                return r
-                    .as_ref()
-                    .map(|a| a.clone())
-                    .map_err(|err| eyre!("Cache value Error: {err}"));
+            if let Some(slot) = values.get(key) {
+                slot.last_used.store(tick, Ordering::Relaxed);
+                return slot.clone();
             }
         }
 
         let mut values = self.values.write().await;
-        values.insert(key.clone(), Arc::default());
-        let mutex = values.get(key).unwrap().clone();
-        // Lock interior mutex before dropping lock on values
+        // NOTE(emily): Someone else might have inserted this key whilst we were waiting
+        // for the write lock, so dont blindly overwrite it
+        if let Some(slot) = values.get(key) {
+            slot.last_used.store(tick, Ordering::Relaxed);
+            return slot.clone();
+        }
+
+        let slot = Arc::new(Slot::new(tick));
+        values.insert(key.clone(), slot.clone());
+        self.evict(&mut values);
+
+        slot
+    }
+
+    fn evict(&self, values: &mut HashMap<K, Arc<Slot<V>>>) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+
+        while values.len() > capacity {
+            // Find the least recently used slot that nobody is currently filling in
+            let oldest = values
+                .iter()
+                .filter(|(_, slot)| slot.stored.try_lock().is_ok())
+                .min_by_key(|(_, slot)| slot.last_used.load(Ordering::Relaxed))
+                .map(|(k, _)| k.clone());
+
+            match oldest {
+                Some(key) => {
+                    values.remove(&key);
+                    self.counters.evictions.fetch_add(1, Ordering::Relaxed);
+                }
+                // Everything is in flight, allow going over capacity for now
+                None => break,
+            }
+        }
+    }
+
+    async fn fill<F>(&self, stored: &mut Option<Stored<V>>, f: F) -> Result<Arc<V>>
+    where
+        F: Future<Output = Result<Arc<V>>> + Send,
+    {
+        self.counters.misses.fetch_add(1, Ordering::Relaxed);
+
+        let value = f.await.map_err(|err| {
+            self.counters.errors.fetch_add(1, Ordering::Relaxed);
+            CachedError(Arc::new(err))
+        });
+
+        let stored = stored.insert(Stored {
+            value,
+            stored_at: Instant::now(),
+        });
+        Self::cached_result(stored)
+    }
+
+    pub async fn get<F>(&self, key: &K, f: F) -> Result<Arc<V>>
+    where
+        F: Future<Output = Result<Arc<V>>> + Send,
+    {
+        let slot = self.slot(key).await;
 
-        let mut mutex = mutex.lock().await;
-        drop(values);
+        // Only one caller at a time holds the slot, so only one of them will evaluate f.
+        // Everyone else waiting on the slot will see the value that was stored.
+        let mut stored = slot.stored.lock().await;
+
+        if let Some(stored) = stored.as_ref().filter(|stored| self.is_fresh(stored)) {
+            self.counters.hits.fetch_add(1, Ordering::Relaxed);
+            return Self::cached_result(stored);
+        }
 
-        // Now that we hold lock on the values mutex, but not the hashmap, we can eval f
-        let v = f.await;
-        *mutex = Some(
-            v.as_ref()
-                .map(|v| v.clone())
-                .map_err(|err| eyre!("Cache value Error: {err}")),
-        );
-        v
+        self.fill(&mut stored, f).await
+    }
+
+    /// Re-evaluate f for key regardless of whether there is a fresh value already
+    pub async fn refresh<F>(&self, key: &K, f: F) -> Result<Arc<V>>
+    where
+        F: Future<Output = Result<Arc<V>>> + Send,
+    {
+        let slot = self.slot(key).await;
+        let mut stored = slot.stored.lock().await;
+        self.fill(&mut stored, f).await
     }
 
     pub async fn write(&self, key: K, v: Arc<V>) {
-        self.values
-            .write()
+        let slot = self.slot(&key).await;
+        *slot.stored.lock().await = Some(Stored {
+            value: Ok(v),
+            stored_at: Instant::now(),
+        });
+    }
+
+    /// Whether there is a fresh, successful value for key
+    pub async fn contains(&self, key: &K) -> bool {
+        let Some(slot) = self.values.read().await.get(key).cloned() else {
+            return false;
+        };
+
+        let stored = slot.stored.lock().await;
+        matches!(stored.as_ref(), Some(stored) if stored.value.is_ok() && self.is_fresh(stored))
+    }
+
+    /// Remove key so that the next get evaluates again
+    pub async fn invalidate(&self, key: &K) {
+        self.values.write().await.remove(key);
+    }
+
+    pub async fn len(&self) -> usize {
+        self.values.read().await.len()
+    }
+
+    pub fn metrics(&self) -> CacheMetrics {
+        CacheMetrics {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+            errors: self.counters.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    /// A lookup that counts how many times it was actually run
+    fn lookup(calls: &Arc<AtomicUsize>, value: u32) -> impl Future<Output = Result<Arc<u32>>> {
+        let calls = calls.clone();
+        async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok(Arc::new(value))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrent_gets_only_look_up_once() {
+        let cache = Arc::new(Cache::<u32, u32>::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..10)
+            .map(|_| {
+                let cache = cache.clone();
+                let calls = calls.clone();
+                tokio::spawn(async move { *cache.get(&1, lookup(&calls, 5)).await.unwrap() })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.await.unwrap(), 5);
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let metrics = cache.metrics();
+        assert_eq!(metrics.misses, 1);
+        assert_eq!(metrics.hits, 9);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn values_expire_after_ttl() {
+        let cache = Cache::<u32, u32>::new().with_ttl(Duration::from_secs(60));
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        assert_eq!(*cache.get(&1, lookup(&calls, 1)).await.unwrap(), 1);
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(*cache.get(&1, lookup(&calls, 2)).await.unwrap(), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(Duration::from_secs(31)).await;
+        assert_eq!(*cache.get(&1, lookup(&calls, 2)).await.unwrap(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn errors_are_retried_after_error_ttl() {
+        let cache = Cache::<u32, u32>::new().with_error_ttl(Duration::from_secs(5));
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        assert!(cache
+            .get(&1, async { Err(eyre::eyre!("offline")) })
             .await
-            .insert(key, Arc::new(Mutex::new(Some(Ok(v)))));
+            .is_err());
+
+        // Still remembered, so we dont hammer whatever just failed
+        assert!(cache.get(&1, lookup(&calls, 1)).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        tokio::time::advance(Duration::from_secs(6)).await;
+        assert_eq!(*cache.get(&1, lookup(&calls, 1)).await.unwrap(), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.metrics().errors, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn least_recently_used_is_evicted() {
+        let cache = Cache::<u32, u32>::new().with_capacity(2);
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        cache.get(&1, lookup(&calls, 1)).await.unwrap();
+        cache.get(&2, lookup(&calls, 2)).await.unwrap();
+        // Touch 1 so that 2 is now the oldest
+        cache.get(&1, lookup(&calls, 1)).await.unwrap();
+        cache.get(&3, lookup(&calls, 3)).await.unwrap();
+
+        assert!(cache.contains(&1).await);
+        assert!(!cache.contains(&2).await);
+        assert!(cache.contains(&3).await);
+        assert_eq!(cache.len().await, 2);
+        assert_eq!(cache.metrics().evictions, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refresh_ignores_fresh_values() {
+        let cache = Cache::<u32, u32>::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        cache.get(&1, lookup(&calls, 1)).await.unwrap();
+        assert_eq!(*cache.refresh(&1, lookup(&calls, 2)).await.unwrap(), 2);
+        assert_eq!(*cache.get(&1, lookup(&calls, 3)).await.unwrap(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn invalidate_forgets_the_value() {
+        let cache = Cache::<u32, u32>::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        cache.get(&1, lookup(&calls, 1)).await.unwrap();
+        cache.invalidate(&1).await;
+        assert_eq!(*cache.get(&1, lookup(&calls, 2)).await.unwrap(), 2);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Gone;
+
+    impl fmt::Display for Gone {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "gone")
+        }
+    }
+
+    impl std::error::Error for Gone {}
+
+    #[tokio::test(start_paused = true)]
+    async fn cached_errors_keep_the_original_error() {
+        let cache = Cache::<u32, u32>::new();
+
+        for _ in 0..2 {
+            let err = cache
+                .get(&1, async { Err(Report::new(Gone)) })
+                .await
+                .unwrap_err();
+            assert_eq!(err.to_string(), "gone");
+            assert_eq!(
+                err.chain().find_map(|e| e.downcast_ref::<Gone>()),
+                Some(&Gone)
+            );
+        }
+        assert_eq!(cache.metrics().hits, 1);
     }
 }
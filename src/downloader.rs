@@ -1,19 +1,25 @@
-use crate::model;
+use crate::{model, retry};
 use async_trait::async_trait;
 use eyre::{eyre, Result};
 use log::warn;
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
 pub(crate) struct Downloader {
     pub(crate) client: reqwest::Client,
     pub(crate) store: Arc<model::Store>,
+    /// Refuse to play songs that are only previews, so that the player skips them
+    pub(crate) skip_previews: AtomicBool,
 }
 
 impl Downloader {
     pub(crate) fn new(store: Arc<model::Store>) -> Self {
         Self {
-            client: reqwest::Client::new(),
+            client: store.client().clone(),
             store,
+            skip_previews: AtomicBool::new(false),
         }
     }
 }
@@ -21,7 +27,7 @@ impl Downloader {
 #[async_trait]
 impl audio::Downloader for Downloader {
     async fn download_chunk(&self, url: &str) -> Result<Vec<u8>> {
-        let response = self.client.get(url).send().await?;
+        let response = retry::policy().send(self.client.get(url)).await?;
         // make sure that if the server returns an error (e.g. Forbidden)
         // that we pass it back up to whoever called us
         Ok(response.error_for_status()?.bytes().await?.to_vec())
@@ -29,24 +35,52 @@ impl audio::Downloader for Downloader {
 
     async fn playlist(&self, id: audio::SongId) -> Result<String> {
         // Try and get mpeg transcoding from song
-        let (title, transcodings) = self
-            .store
-            .song(&id)
-            .await
-            .map(|song| (song.title.clone(), song.media.transcodings.clone()))?;
-
-        if let Some(transcoding) = transcodings
-            .iter()
-            .find(|t| t.format.mime_type == "audio/mpeg")
+        let song = self.store.song(&id).await?;
+        if !song.availability.is_playable() {
+            return Err(eyre!(
+                "Song {} is not playable ({})",
+                &song.title,
+                song.availability.description()
+            ));
+        }
+
+        if song.availability == model::Availability::Snippet
+            && self.skip_previews.load(Ordering::Relaxed)
         {
-            let result = transcoding.resolve().await;
+            return Err(eyre!("Song {} is only a preview, skipping", &song.title));
+        }
+
+        if let Some(transcoding) = song.media.preferred("audio/mpeg") {
+            let result = transcoding.resolve(&self.client).await;
             Ok(result?)
         } else {
             warn!(
                 "Song {} missing mpeg transcoding (available transcodings were {:?})",
-                &title, transcodings
+                &song.title, song.media.transcodings
             );
             Err(eyre!("No such mpeg transcoding for SongId {}", id))
         }
     }
+
+    async fn related(&self, ids: &[audio::SongId]) -> Result<Vec<audio::SongId>> {
+        let skip_previews = self.skip_previews.load(Ordering::Relaxed);
+
+        let mut related = vec![];
+        for id in ids {
+            match self.store.related(id).await {
+                Ok(songs) => related.push(songs),
+                Err(err) => warn!("Failed to get songs related to {id} ({:?})", err),
+            }
+        }
+
+        // NOTE(emily): Take turns between each song, so that the best matches for all
+        // of them come first rather than everything related to just one of them
+        let longest = related.iter().map(|songs| songs.len()).max().unwrap_or(0);
+        Ok((0..longest)
+            .flat_map(|i| related.iter().filter_map(move |songs| songs.get(i)))
+            .filter(|song| song.availability.is_playable())
+            .filter(|song| !(skip_previews && song.availability == model::Availability::Snippet))
+            .map(|song| song.id)
+            .collect())
+    }
 }
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use eyre::Result;
+
+const USER_AGENT: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:88.0) Gecko/20100101 Firefox/88.0";
+
+/// Settings for the one reqwest::Client that everything shares
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    /// Timeout for a whole request, including reading the body
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    /// Proxy url to send everything through. When this is None the usual
+    /// HTTP_PROXY / HTTPS_PROXY environment variables are still respected.
+    pub proxy: Option<String>,
+    pub user_agent: String,
+    /// Allow negotiating HTTP/2, otherwise only HTTP/1 is used
+    pub http2: bool,
+    pub max_idle_per_host: usize,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            proxy: std::env::var("STRATUS_PROXY").ok(),
+            user_agent: USER_AGENT.into(),
+            http2: true,
+            max_idle_per_host: 16,
+        }
+    }
+}
+
+impl HttpConfig {
+    pub fn build(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .user_agent(self.user_agent.as_str())
+            .pool_max_idle_per_host(self.max_idle_per_host);
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+
+        if !self.http2 {
+            builder = builder.http1_only();
+        }
+
+        Ok(builder.build()?)
+    }
+}
@@ -0,0 +1,258 @@
+use std::{collections::HashMap, sync::Arc};
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+use crate::model::{Id, Playlist, Song, User};
+
+/// How many results of each kind to keep
+pub const MAX_RESULTS: usize = 50;
+
+/// Everything that the Store has resolved so far, by id. The Store adds to this as it goes.
+#[derive(Debug, Default)]
+pub struct Library {
+    songs: HashMap<Id, Arc<Song>>,
+    users: HashMap<Id, Arc<User>>,
+    playlists: HashMap<Id, Arc<Playlist>>,
+}
+
+/// Best matches for a query, best first
+#[derive(Debug, Default, Clone)]
+pub struct LibraryResults {
+    pub query: String,
+    pub songs: Vec<Arc<Song>>,
+    pub users: Vec<Arc<User>>,
+    pub playlists: Vec<Arc<Playlist>>,
+}
+
+impl LibraryResults {
+    pub fn is_empty(&self) -> bool {
+        self.songs.is_empty() && self.users.is_empty() && self.playlists.is_empty()
+    }
+}
+
+impl Library {
+    // NOTE(emily): Anything with a fake (negative) id is a stand in for something
+    // that we dont know, or something we made up like search results, so those are left out
+
+    pub fn add_user(&mut self, user: &Arc<User>) {
+        if user.id > 0 {
+            self.users.insert(user.id, user.clone());
+        }
+    }
+
+    pub fn add_song(&mut self, song: &Arc<Song>) {
+        if song.id > 0 {
+            self.songs.insert(song.id, song.clone());
+        }
+    }
+
+    pub fn add_playlist(&mut self, playlist: &Arc<Playlist>) {
+        if playlist.id > 0 {
+            self.playlists.insert(playlist.id, playlist.clone());
+        }
+    }
+
+    /// An empty query matches nothing
+    pub fn search(&self, query: &str) -> LibraryResults {
+        if query.trim().is_empty() {
+            return LibraryResults {
+                query: query.to_string(),
+                ..Default::default()
+            };
+        }
+
+        let matcher = SkimMatcherV2::default();
+
+        LibraryResults {
+            query: query.to_string(),
+            songs: best(&self.songs, |song| {
+                sum_scores(
+                    matcher.fuzzy_match(&song.title, query),
+                    matcher.fuzzy_match(&song.user.username, query),
+                )
+            }),
+            users: best(&self.users, |user| {
+                matcher.fuzzy_match(&user.username, query)
+            }),
+            playlists: best(&self.playlists, |playlist| {
+                sum_scores(
+                    matcher.fuzzy_match(&playlist.title, query),
+                    matcher.fuzzy_match(&playlist.user.username, query),
+                )
+            }),
+        }
+    }
+}
+
+fn sum_scores(a: Option<i64>, b: Option<i64>) -> Option<i64> {
+    match (a, b) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or_default() + b.unwrap_or_default()),
+    }
+}
+
+/// The MAX_RESULTS items that score highest
+fn best<T>(items: &HashMap<Id, Arc<T>>, score: impl Fn(&T) -> Option<i64>) -> Vec<Arc<T>> {
+    let mut scored: Vec<(i64, Id, &Arc<T>)> = items
+        .iter()
+        .filter_map(|(id, item)| Some((score(item)?, *id, item)))
+        .collect();
+
+    // NOTE(emily): Ties are broken by id so that results dont jump around between keystrokes
+    scored.sort_by_key(|(score, id, _)| (-score, *id));
+    scored.truncate(MAX_RESULTS);
+
+    scored
+        .into_iter()
+        .map(|(_, _, item)| item.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Availability, Eventually};
+
+    fn user(id: Id, username: &str) -> Arc<User> {
+        Arc::new(User {
+            id,
+            username: username.into(),
+            ..Default::default()
+        })
+    }
+
+    fn song(id: Id, title: &str, user: &Arc<User>) -> Arc<Song> {
+        Arc::new(Song {
+            title: title.into(),
+            user: user.clone(),
+            ..Song::unavailable(id, Availability::Available)
+        })
+    }
+
+    fn playlist(id: Id, title: &str, user: &Arc<User>) -> Arc<Playlist> {
+        Arc::new(Playlist {
+            id,
+            permalink: None,
+            uri: None,
+            user: user.clone(),
+            artwork_url: None,
+            artwork: Eventually::never(),
+            title: title.into(),
+            songs: vec![],
+            added_at: vec![],
+            set_type: None,
+            is_album: false,
+            track_count: None,
+            duration: None,
+            release_date: None,
+            created_at: None,
+            description: None,
+        })
+    }
+
+    fn ids<T>(items: &[Arc<T>], id: impl Fn(&T) -> Id) -> Vec<Id> {
+        items.iter().map(|item| id(item)).collect()
+    }
+
+    fn library() -> Library {
+        let mut library = Library::default();
+        let emily = user(1, "emily");
+        let someone = user(2, "someone else");
+
+        library.add_user(&emily);
+        library.add_user(&someone);
+        library.add_song(&song(10, "deep blue", &emily));
+        library.add_song(&song(11, "the deep end", &someone));
+        library.add_song(&song(12, "shallow", &someone));
+        library.add_playlist(&playlist(20, "deep cuts", &someone));
+        library.add_playlist(&playlist(21, "mornings", &emily));
+        library
+    }
+
+    #[test]
+    fn empty_query_matches_nothing() {
+        let library = library();
+
+        for query in ["", "   "] {
+            let results = library.search(query);
+            assert!(results.is_empty());
+            assert_eq!(results.query, query);
+        }
+    }
+
+    #[test]
+    fn only_things_with_real_ids_are_indexed() {
+        let mut library = Library::default();
+        let emily = user(1, "emily");
+
+        library.add_user(&user(-1, "emily"));
+        library.add_song(&song(-2, "emily", &emily));
+        library.add_playlist(&playlist(-3, "emily", &emily));
+        assert!(library.search("emily").is_empty());
+
+        // Adding something again replaces it rather than adding it twice
+        library.add_user(&emily);
+        library.add_user(&user(1, "emily"));
+        assert_eq!(ids(&library.search("emily").users, |u| u.id), [1]);
+    }
+
+    #[test]
+    fn matches_titles_and_usernames() {
+        let results = library().search("deep");
+
+        let mut songs = ids(&results.songs, |s| s.id);
+        songs.sort();
+        assert_eq!(songs, [10, 11]);
+        assert_eq!(ids(&results.playlists, |p| p.id), [20]);
+        assert!(results.users.is_empty());
+
+        // Songs and playlists also match on who made them
+        let results = library().search("emily");
+        assert_eq!(ids(&results.users, |u| u.id), [1]);
+        assert_eq!(ids(&results.songs, |s| s.id), [10]);
+        assert_eq!(ids(&results.playlists, |p| p.id), [21]);
+    }
+
+    #[test]
+    fn best_matches_come_first() {
+        let mut library = Library::default();
+        let emily = user(1, "emily");
+        let someone = user(2, "someone");
+
+        library.add_song(&song(10, "d e e p", &someone));
+        library.add_song(&song(11, "deep", &someone));
+        // Matching on both title and artist beats just the title
+        library.add_song(&song(12, "emily", &emily));
+        library.add_song(&song(13, "emily", &someone));
+
+        assert_eq!(ids(&library.search("deep").songs, |s| s.id), [11, 10]);
+        assert_eq!(ids(&library.search("emily").songs, |s| s.id), [12, 13]);
+    }
+
+    #[test]
+    fn ties_are_broken_by_id() {
+        let mut library = Library::default();
+        let emily = user(1, "emily");
+
+        for id in [5, 3, 4, 1, 2] {
+            library.add_song(&song(id, "same", &emily));
+        }
+
+        assert_eq!(
+            ids(&library.search("same").songs, |s| s.id),
+            [1, 2, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn results_are_capped() {
+        let mut library = Library::default();
+        let emily = user(1, "emily");
+
+        for id in 1..=(MAX_RESULTS as Id * 2) {
+            library.add_song(&song(id, "same", &emily));
+        }
+
+        assert_eq!(library.search("same").songs.len(), MAX_RESULTS);
+    }
+}
@@ -1,7 +1,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 mod cache;
 mod downloader;
+mod http;
+mod library;
 mod model;
+mod retry;
 mod sc;
 mod ui_egui;
 mod ui_iced;
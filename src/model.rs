@@ -1,10 +1,24 @@
 use crate::sc;
-use crate::{cache::Cache, sc::SoundCloud};
+use crate::{
+    cache::Cache,
+    library::{Library, LibraryResults},
+    sc::SoundCloud,
+};
 use eyre::Result;
-use parking_lot::Mutex;
+use futures::StreamExt;
+use log::{info, warn};
+use parking_lot::{Mutex, RwLock};
 
-use std::sync::Arc;
-use tokio::sync::watch;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::{watch, Semaphore};
+
+pub use sc::ChartKind;
 
 pub type Id = i64;
 
@@ -27,6 +41,7 @@ impl From<sc::api::model::Format> for Format {
 pub struct Transcoding {
     pub url: String,
     pub format: Format,
+    pub snipped: bool,
 }
 
 impl From<sc::api::model::Transcoding> for Transcoding {
@@ -34,21 +49,37 @@ impl From<sc::api::model::Transcoding> for Transcoding {
         Self {
             url: value.url,
             format: value.format.into(),
+            snipped: value.snipped,
         }
     }
 }
 
 impl Transcoding {
-    pub async fn resolve(&self) -> Result<String> {
-        Ok(sc::api::hls_playlist(&self.url).await?)
+    pub async fn resolve(&self, client: &reqwest::Client) -> Result<String> {
+        Ok(sc::api::hls_playlist(client, &self.url).await?)
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Media {
     pub transcodings: Vec<Transcoding>,
 }
 
+impl Media {
+    /// Best transcoding for mime_type, full songs are preferred over previews
+    pub fn preferred(&self, mime_type: &str) -> Option<&Transcoding> {
+        self.transcodings
+            .iter()
+            .filter(|t| t.format.mime_type == mime_type)
+            .min_by_key(|t| t.snipped)
+    }
+
+    /// Whether we can only play a preview of the song
+    pub fn snipped_only(&self) -> bool {
+        !self.transcodings.is_empty() && self.transcodings.iter().all(|t| t.snipped)
+    }
+}
+
 impl From<sc::Media> for Media {
     fn from(value: sc::Media) -> Self {
         Self {
@@ -57,7 +88,7 @@ impl From<sc::Media> for Media {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct User {
     pub id: i64,
     pub permalink: Option<String>,
@@ -65,7 +96,76 @@ pub struct User {
 
     pub username: String,
     pub avatar_url: Option<String>,
-    pub avatar: Option<Arc<iced::widget::image::Handle>>,
+    pub avatar: Eventually<iced::widget::image::Handle>,
+
+    // NOTE(emily): These are only known once the user has been looked up on their own,
+    // users attached to songs and playlists dont have them
+    pub followers: Option<u64>,
+    pub followings: Option<u64>,
+    pub description: Option<String>,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub verified: bool,
+    pub track_count: Option<u64>,
+    pub likes: Option<u64>,
+}
+
+/// Whether a Song can actually be played
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Availability {
+    Available,
+    /// Not available in our region
+    Blocked,
+    /// Only a short preview can be played
+    Snippet,
+    /// Deleted or made private
+    Removed,
+    Failed {
+        reason: String,
+    },
+}
+
+impl Availability {
+    pub fn from_error(err: &eyre::Report) -> Self {
+        // NOTE(emily): Errors that come out of a Cache wrap the original, so look through the chain
+        match err
+            .chain()
+            .find_map(|err| err.downcast_ref::<sc::api::ApiError>())
+        {
+            Some(sc::api::ApiError::NotFound) => Self::Removed,
+            Some(sc::api::ApiError::Forbidden) => Self::Blocked,
+            _ => Self::Failed {
+                reason: err.to_string(),
+            },
+        }
+    }
+
+    fn from_sc(policy: sc::api::model::Policy, media: &Media) -> Self {
+        use sc::api::model::Policy;
+
+        match policy {
+            Policy::Block => Self::Blocked,
+            // NOTE(emily): Trust the transcodings over the policy, sometimes there
+            // is a full transcoding available even for SNIP songs
+            _ if media.snipped_only() => Self::Snippet,
+            Policy::Snip if media.transcodings.is_empty() => Self::Snippet,
+            _ => Self::Available,
+        }
+    }
+
+    pub fn is_playable(&self) -> bool {
+        matches!(self, Self::Available | Self::Snippet)
+    }
+
+    pub fn description(&self) -> &str {
+        match self {
+            Self::Available => "Available",
+            Self::Blocked => "Not available in your region",
+            Self::Snippet => "Preview only",
+            Self::Removed => "Removed",
+            Self::Failed { reason } => reason,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -76,10 +176,136 @@ pub struct Song {
 
     pub user: Arc<User>,
     pub artwork_url: Option<String>,
-    pub artwork: Option<Arc<iced::widget::image::Handle>>,
+    pub artwork: Eventually<iced::widget::image::Handle>,
     pub title: String,
     pub media: Media,
     pub full_duration: usize,
+    pub policy: sc::api::model::Policy,
+    pub monetization_model: Option<String>,
+    pub availability: Availability,
+    /// ISO 8601 timestamp of when the song was uploaded
+    pub created_at: Option<String>,
+    pub plays: Option<u64>,
+    pub likes: Option<u64>,
+    pub genre: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub release_date: Option<String>,
+    pub label: Option<String>,
+    pub reposts: Option<u64>,
+    pub comments: Option<u64>,
+    pub license: Option<String>,
+    pub purchase_url: Option<String>,
+    pub waveform_url: Option<String>,
+}
+
+impl Song {
+    /// Stand in for a song that we could not resolve
+    pub fn unavailable(id: Id, availability: Availability) -> Self {
+        Self {
+            id,
+            permalink: None,
+            uri: None,
+            user: Arc::default(),
+            artwork_url: None,
+            artwork: Eventually::never(),
+            title: "Unavailable track".into(),
+            media: Media::default(),
+            full_duration: 0,
+            policy: Default::default(),
+            monetization_model: None,
+            availability,
+            created_at: None,
+            plays: None,
+            likes: None,
+            genre: None,
+            description: None,
+            tags: vec![],
+            release_date: None,
+            label: None,
+            reposts: None,
+            comments: None,
+            license: None,
+            purchase_url: None,
+            waveform_url: None,
+        }
+    }
+}
+
+/// Split a SoundCloud tag list, e.g. `electronic "deep house" chill`
+fn parse_tag_list(tag_list: &str) -> Vec<String> {
+    let mut tags = vec![];
+    let mut rest = tag_list.trim_start();
+
+    while !rest.is_empty() {
+        let (tag, after) = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => rest.split_once(char::is_whitespace).unwrap_or((rest, "")),
+        };
+
+        if !tag.is_empty() {
+            tags.push(tag.to_string());
+        }
+        rest = after.trim_start();
+    }
+
+    tags
+}
+
+/// Loudness of a song over time
+#[derive(Debug, Clone)]
+pub struct Waveform {
+    /// Evenly spaced over the song, between 0.0 and 1.0
+    pub samples: Vec<f32>,
+}
+
+impl From<sc::api::model::Waveform> for Waveform {
+    fn from(waveform: sc::api::model::Waveform) -> Self {
+        let height = waveform.height.max(1) as f32;
+        Self {
+            samples: waveform
+                .samples
+                .into_iter()
+                .map(|sample| (sample as f32 / height).min(1.0))
+                .collect(),
+        }
+    }
+}
+
+/// A comment on a song
+#[derive(Debug, Clone)]
+pub struct Comment {
+    pub id: Id,
+    pub body: String,
+    // NOTE(emily): Comments can come from thousands of different users, so we only keep
+    // what we need to show them rather than resolving (and loading avatars for) every one
+    pub user_id: Id,
+    pub username: String,
+    /// Milliseconds into the song, None for comments that are not at a point in the song
+    pub timestamp: Option<usize>,
+    pub created_at: Option<String>,
+}
+
+impl From<sc::Comment> for Comment {
+    fn from(comment: sc::Comment) -> Self {
+        Self {
+            id: comment.object.id,
+            body: comment.body,
+            user_id: comment.user.object.id,
+            username: comment.user.username,
+            timestamp: comment.timestamp,
+            created_at: comment.created_at,
+        }
+    }
+}
+
+/// Just enough of a playlist to show and link to it
+#[derive(Debug, Clone)]
+pub struct PlaylistSummary {
+    pub id: Id,
+    pub title: String,
+    pub username: String,
+    pub track_count: Option<usize>,
 }
 
 #[derive(Debug)]
@@ -90,47 +316,280 @@ pub struct Playlist {
 
     pub user: Arc<User>,
     pub artwork_url: Option<String>,
-    pub artwork: Option<Arc<iced::widget::image::Handle>>,
+    pub artwork: Eventually<iced::widget::image::Handle>,
     pub title: String,
     pub songs: Vec<Arc<Song>>,
+    /// ISO 8601 timestamp of when each song was added (or liked, reposted...), in the same
+    /// order as songs. Empty when we dont know.
+    pub added_at: Vec<Option<String>>,
+
+    /// e.g. album, ep, single. None for plain playlists.
+    pub set_type: Option<String>,
+    pub is_album: bool,
+    /// How many songs SoundCloud says are in the playlist, which can be more than we
+    /// managed to load
+    pub track_count: Option<usize>,
+    /// In milliseconds
+    pub duration: Option<usize>,
+    pub release_date: Option<String>,
+    pub created_at: Option<String>,
+    pub description: Option<String>,
 }
 
+impl Playlist {
+    /// e.g. "Album", "Playlist"
+    pub fn kind(&self) -> String {
+        match self.set_type.as_deref() {
+            Some(set_type) if !set_type.is_empty() => {
+                let mut chars = set_type.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            }
+            _ if self.is_album => "Album".into(),
+            _ => "Playlist".into(),
+        }
+    }
+}
+
+/// Key for the collections of songs that the Store caches.
+/// Each kind of collection gets its own namespace so that e.g. a users likes
+/// and their tracks never end up sharing an entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CollectionKey {
+    Likes(Id),
+    Tracks(Id),
+    Reposts(Id),
+    Search(String),
+    /// Chart and genre, e.g. `electronic`
+    Chart(ChartKind, String),
+    Tag(String),
+}
+
+/// The collections of songs that every user has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UserCollection {
+    Likes,
+    Tracks,
+    Reposts,
+}
+
+impl CollectionKey {
+    /// Key for collection of the user with id
+    pub fn user(id: Id, collection: UserCollection) -> Self {
+        match collection {
+            UserCollection::Likes => CollectionKey::Likes(id),
+            UserCollection::Tracks => CollectionKey::Tracks(id),
+            UserCollection::Reposts => CollectionKey::Reposts(id),
+        }
+    }
+}
+
+/// Key for the lists of users that the Store caches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UserListKey {
+    Followers(Id),
+    Followings(Id),
+}
+
+/// Collections change as people like and upload things, so dont hold on to them forever
+const COLLECTION_TTL: Duration = Duration::from_secs(10 * 60);
+const IMAGE_CACHE_CAPACITY: usize = 4096;
+
+/// How big an image is going to be shown, so that we can ask SoundCloud for
+/// a version that is about the right size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageSize {
+    /// Rows in song lists and user grids
+    ListThumb,
+    Controls,
+    /// The big image at the top of a track or user page
+    Hero,
+}
+
+impl ImageSize {
+    /// Largest width or height that the image is going to be shown at
+    pub fn pixels(self) -> u32 {
+        match self {
+            ImageSize::ListThumb => 150,
+            ImageSize::Controls => 75,
+            ImageSize::Hero => 500,
+        }
+    }
+
+    /// Smallest SoundCloud variant that is at least as big as pixels
+    fn variant(self) -> &'static str {
+        match self {
+            ImageSize::ListThumb => "t300x300",
+            // NOTE(emily): large is 100x100, and what SoundCloud gives us urls for anyway
+            ImageSize::Controls => "large",
+            ImageSize::Hero => "t500x500",
+        }
+    }
+
+    /// Rewrite a SoundCloud artwork url to point at the variant for this size
+    pub fn url(self, url: &str) -> String {
+        url.replace("-large", &format!("-{}", self.variant()))
+    }
+}
+
+/// At most max of items, picked evenly from all of them
+fn spread<T>(items: Vec<T>, max: usize) -> Vec<T> {
+    let len = items.len();
+    if len <= max {
+        return items;
+    }
+
+    let mut items: Vec<Option<T>> = items.into_iter().map(Some).collect();
+    (0..max)
+        .filter_map(|i| items[i * len / max].take())
+        .collect()
+}
+
+fn decode_image(bytes: Vec<u8>, size: ImageSize) -> Result<iced::widget::image::Handle> {
+    use image::GenericImageView;
+
+    let pixels = size.pixels();
+    let mut image = image::load_from_memory(&bytes)?;
+    if image.width() > pixels || image.height() > pixels {
+        image = image.resize(pixels, pixels, image::imageops::FilterType::Triangle);
+    }
+
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    Ok(iced::widget::image::Handle::from_pixels(
+        width,
+        height,
+        rgba.into_raw(),
+    ))
+}
+
+/// How many songs to ask for at once with tracks?ids=
+const SONG_BATCH_SIZE: usize = 50;
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+const MAX_CONCURRENT_IMAGES: usize = 8;
+/// Images that finish loading within this long of each other only cause one redraw
+const IMAGE_REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const WAVEFORM_CACHE_CAPACITY: usize = 256;
+/// Some songs have tens of thousands of comments, we dont need to keep all of them
+const MAX_COMMENTS: usize = 1000;
+/// Popular users have millions of followers, only show the first few pages
+const MAX_USER_LIST: usize = 1000;
+
 pub struct Store {
     soundcloud: Arc<SoundCloud>,
     user_cache: Cache<Id, User>,
     song_cache: Cache<Id, Song>,
     playlist_cache: Cache<Id, Playlist>,
-    likes_cache: Cache<Id, Playlist>,
-    image_cache: Cache<String, iced::widget::image::Handle>,
+    collection_cache: Cache<CollectionKey, Playlist>,
+    user_playlists_cache: Cache<Id, Vec<Arc<Playlist>>>,
+    /// Playlists that each song is in
+    song_playlists_cache: Cache<Id, Vec<PlaylistSummary>>,
+    waveform_cache: Cache<Id, Waveform>,
+    comments_cache: Cache<Id, Vec<Comment>>,
+    user_list_cache: Cache<UserListKey, Vec<Arc<User>>>,
+    related_cache: Cache<Id, Vec<Arc<Song>>>,
+    image_cache: Arc<Cache<(String, ImageSize), iced::widget::image::Handle>>,
+    /// Index of every user, song and playlist resolved so far, for search_library
+    library: Arc<RwLock<Library>>,
+    /// Limits how many images we download at once
+    image_permits: Arc<Semaphore>,
+    /// Bumped after images finish loading, so that the UI knows to redraw
+    images_loaded: Arc<watch::Sender<usize>>,
+    /// Whether images_loaded is about to be bumped already
+    images_redraw_pending: Arc<AtomicBool>,
+    /// Lazy images are requested from the UI thread, which is not inside the runtime
+    runtime: tokio::runtime::Handle,
 }
 
 impl Store {
-    pub fn new() -> Self {
+    pub fn new(client: reqwest::Client) -> Self {
         Self {
-            soundcloud: Arc::new(SoundCloud::new()),
+            soundcloud: Arc::new(SoundCloud::new(client)),
             user_cache: Default::default(),
             song_cache: Default::default(),
             playlist_cache: Default::default(),
-            likes_cache: Default::default(),
-            image_cache: Default::default(),
+            collection_cache: Cache::new().with_ttl(COLLECTION_TTL),
+            user_playlists_cache: Cache::new().with_ttl(COLLECTION_TTL),
+            song_playlists_cache: Cache::new().with_ttl(COLLECTION_TTL),
+            // NOTE(emily): Missing waveforms are common and not worth asking for again straight away
+            waveform_cache: Cache::new()
+                .with_capacity(WAVEFORM_CACHE_CAPACITY)
+                .with_error_ttl(COLLECTION_TTL),
+            comments_cache: Cache::new().with_ttl(COLLECTION_TTL),
+            user_list_cache: Cache::new().with_ttl(COLLECTION_TTL),
+            related_cache: Cache::new().with_ttl(COLLECTION_TTL),
+            image_cache: Arc::new(Cache::new().with_capacity(IMAGE_CACHE_CAPACITY)),
+            library: Default::default(),
+            image_permits: Arc::new(Semaphore::new(MAX_CONCURRENT_IMAGES)),
+            images_loaded: Arc::new(watch::channel(0).0),
+            images_redraw_pending: Default::default(),
+            runtime: tokio::runtime::Handle::current(),
         }
     }
 
+    /// Log how big each cache is and how well it is doing
+    pub async fn log_cache_metrics(&self) {
+        macro_rules! log_cache {
+            ($($name:ident),*) => {
+                $(
+                    let metrics = self.$name.metrics();
+                    info!(
+                        "{}: {} entries, {} hits, {} misses, {} evictions, {} errors",
+                        stringify!($name),
+                        self.$name.len().await,
+                        metrics.hits,
+                        metrics.misses,
+                        metrics.evictions,
+                        metrics.errors
+                    );
+                )*
+            };
+        }
+
+        log_cache!(
+            user_cache,
+            song_cache,
+            playlist_cache,
+            collection_cache,
+            user_playlists_cache,
+            song_playlists_cache,
+            waveform_cache,
+            comments_cache,
+            user_list_cache,
+            related_cache,
+            image_cache
+        );
+    }
+
+    /// The http client that everything talking to SoundCloud should share
+    pub fn client(&self) -> &reqwest::Client {
+        self.soundcloud.client()
+    }
+
     pub async fn resolve_sc_user(&self, sc_user: sc::api::model::User) -> Result<Arc<User>> {
-        let avatar = if let Some(url) = sc_user.avatar.as_ref() {
-            Some(self.image(&url).await?)
-        } else {
-            None
-        };
+        let avatar = self.lazy_image(sc_user.avatar.as_deref(), ImageSize::ListThumb);
 
-        Ok(Arc::new(User {
+        let user = Arc::new(User {
             id: sc_user.object.id,
             permalink: sc_user.object.url,
             uri: sc_user.object.uri,
             username: sc_user.username,
             avatar: avatar,
             avatar_url: sc_user.avatar,
-        }))
+            followers: sc_user.followers_count,
+            followings: sc_user.followings_count,
+            description: sc_user.description.filter(|d| !d.is_empty()),
+            city: sc_user.city.filter(|c| !c.is_empty()),
+            country: sc_user.country_code,
+            verified: sc_user.verified,
+            track_count: sc_user.track_count,
+            likes: sc_user.likes_count,
+        });
+
+        self.library.write().add_user(&user);
+        Ok(user)
     }
 
     pub async fn user(&self, id: &Id) -> Result<Arc<User>> {
@@ -143,26 +602,116 @@ impl Store {
             .await?)
     }
 
+    async fn fetch_collection(&self, key: &CollectionKey) -> Result<Arc<Playlist>> {
+        let sc_playlist = match key {
+            CollectionKey::Likes(id) => self.soundcloud.likes(sc::Id::Id(*id)).await?,
+            CollectionKey::Tracks(id) => self.soundcloud.songs(sc::Id::Id(*id)).await?,
+            CollectionKey::Reposts(id) => self.soundcloud.reposts(sc::Id::Id(*id)).await?,
+            CollectionKey::Search(query) => self.soundcloud.search(query).await?,
+            CollectionKey::Chart(kind, genre) => self.soundcloud.chart(*kind, genre).await?,
+            CollectionKey::Tag(tag) => self.soundcloud.tag(tag).await?,
+        };
+        self.resolve_sc_playlist(sc_playlist).await
+    }
+
+    pub async fn collection(&self, key: &CollectionKey) -> Result<Arc<Playlist>> {
+        self.collection_cache
+            .get(key, self.fetch_collection(key))
+            .await
+    }
+
+    /// Fetch the collection again even if the cached one is still fresh
+    pub async fn refresh_collection(&self, key: &CollectionKey) -> Result<Arc<Playlist>> {
+        self.collection_cache
+            .refresh(key, self.fetch_collection(key))
+            .await
+    }
+
+    pub async fn user_collection(
+        &self,
+        id: &Id,
+        collection: UserCollection,
+    ) -> Result<Arc<Playlist>> {
+        self.collection(&CollectionKey::user(*id, collection)).await
+    }
+
     pub async fn likes(&self, id: &Id) -> Result<Arc<Playlist>> {
+        self.user_collection(id, UserCollection::Likes).await
+    }
+
+    pub async fn songs(&self, id: &Id) -> Result<Arc<Playlist>> {
+        self.user_collection(id, UserCollection::Tracks).await
+    }
+
+    pub async fn reposts(&self, id: &Id) -> Result<Arc<Playlist>> {
+        self.user_collection(id, UserCollection::Reposts).await
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Arc<Playlist>> {
+        self.collection(&CollectionKey::Search(query.to_owned()))
+            .await
+    }
+
+    /// Search everything that has been resolved so far, without going to SoundCloud
+    // NOTE(emily): Nothing is persisted between runs yet, so this only covers this session
+    pub async fn search_library(&self, query: &str) -> Result<LibraryResults> {
+        let library = self.library.clone();
+        let query = query.to_owned();
+        Ok(tokio::task::spawn_blocking(move || library.read().search(&query)).await?)
+    }
+
+    pub async fn user_playlists(&self, id: &Id) -> Result<Arc<Vec<Arc<Playlist>>>> {
         Ok(self
-            .likes_cache
+            .user_playlists_cache
             .get(id, async {
-                let sc_playlist = self.soundcloud.likes(sc::Id::Id(*id)).await?;
-                self.resolve_sc_playlist(sc_playlist).await
+                let sc_playlists = self.soundcloud.user_playlists(sc::Id::Id(*id)).await?;
+                let mut playlists = vec![];
+                for sc_playlist in sc_playlists {
+                    let playlist = self.resolve_sc_playlist(sc_playlist).await?;
+                    self.playlist_cache
+                        .write(playlist.id, playlist.clone())
+                        .await;
+                    playlists.push(playlist);
+                }
+                Ok(Arc::new(playlists))
             })
             .await?)
     }
 
-    pub async fn songs(&self, id: &Id) -> Result<Arc<Playlist>> {
+    pub async fn user_list(&self, key: &UserListKey) -> Result<Arc<Vec<Arc<User>>>> {
         Ok(self
-            .likes_cache
-            .get(id, async {
-                let sc_playlist = self.soundcloud.songs(sc::Id::Id(*id)).await?;
-                self.resolve_sc_playlist(sc_playlist).await
+            .user_list_cache
+            .get(key, async {
+                let sc_users = match key {
+                    UserListKey::Followers(id) => {
+                        self.soundcloud.followers(*id, MAX_USER_LIST).await?
+                    }
+                    UserListKey::Followings(id) => {
+                        self.soundcloud.followings(*id, MAX_USER_LIST).await?
+                    }
+                };
+
+                // NOTE(emily): These come back fully filled in, so they are as good as
+                // looking each of them up on their own
+                let mut users = vec![];
+                for sc_user in sc_users {
+                    let user = self.resolve_sc_user(sc_user).await?;
+                    self.user_cache.write(user.id, user.clone()).await;
+                    users.push(user);
+                }
+                Ok(Arc::new(users))
             })
             .await?)
     }
 
+    pub async fn followers(&self, id: &Id) -> Result<Arc<Vec<Arc<User>>>> {
+        self.user_list(&UserListKey::Followers(*id)).await
+    }
+
+    pub async fn followings(&self, id: &Id) -> Result<Arc<Vec<Arc<User>>>> {
+        self.user_list(&UserListKey::Followings(*id)).await
+    }
+
     pub async fn song(&self, id: &Id) -> Result<Arc<Song>> {
         Ok(self
             .song_cache
@@ -174,13 +723,11 @@ impl Store {
     }
 
     async fn resolve_sc_song(&self, sc_song: sc::api::model::Song) -> Result<Arc<Song>> {
-        let artwork = if let Some(url) = sc_song.artwork.as_ref() {
-            Some(self.image(&url).await?)
-        } else {
-            None
-        };
+        let artwork = self.lazy_image(sc_song.artwork.as_deref(), ImageSize::ListThumb);
 
-        Ok(Arc::new(Song {
+        let media: Media = sc_song.media.into();
+
+        let song = Arc::new(Song {
             id: sc_song.object.id,
             permalink: sc_song.object.url,
             uri: sc_song.object.uri,
@@ -188,54 +735,200 @@ impl Store {
             artwork: artwork,
             artwork_url: sc_song.artwork,
             title: sc_song.title,
-            media: sc_song.media.into(),
+            availability: Availability::from_sc(sc_song.policy, &media),
+            media,
             full_duration: sc_song.full_duration,
-        }))
+            policy: sc_song.policy,
+            monetization_model: sc_song.monetization_model,
+            created_at: sc_song.created_at,
+            plays: sc_song.playback_count,
+            likes: sc_song.likes_count,
+            // NOTE(emily): Songs without a genre come back as an empty string
+            genre: sc_song.genre.filter(|genre| !genre.is_empty()),
+            description: sc_song.description.filter(|d| !d.is_empty()),
+            tags: sc_song
+                .tag_list
+                .as_deref()
+                .map(parse_tag_list)
+                .unwrap_or_default(),
+            release_date: sc_song.release_date,
+            label: sc_song.label_name.filter(|label| !label.is_empty()),
+            reposts: sc_song.reposts_count,
+            comments: sc_song.comment_count,
+            license: sc_song.license,
+            purchase_url: sc_song.purchase_url,
+            waveform_url: sc_song.waveform_url,
+        });
+
+        self.library.write().add_song(&song);
+        Ok(song)
     }
 
     async fn resolve_sc_playlist(
         &self,
         sc_playlist: sc::api::model::Playlist,
     ) -> Result<Arc<Playlist>> {
-        let artwork = if let Some(url) = sc_playlist.artwork.as_ref() {
-            Some(self.image(&url).await?)
-        } else {
-            None
-        };
+        let artwork = self.lazy_image(sc_playlist.artwork.as_deref(), ImageSize::ListThumb);
 
-        Ok(Arc::new(Playlist {
+        let playlist = Arc::new(Playlist {
             id: sc_playlist.object.id,
             permalink: sc_playlist.object.url,
             uri: sc_playlist.object.uri,
-            user: self.user(&sc_playlist.user.id).await?,
+            // NOTE(emily): Some fake playlists (e.g. search results) dont belong to anyone
+            user: if sc_playlist.user.id > 0 {
+                self.user(&sc_playlist.user.id).await?
+            } else {
+                Arc::default()
+            },
             artwork: artwork,
             artwork_url: sc_playlist.artwork,
             title: sc_playlist.title,
-            songs: futures::future::join_all(sc_playlist.songs.into_iter().map(|song| {
-                async move {
-                    let id = song["id"].as_i64().unwrap();
-                    if let Some(_) = song.get("artwork_url") {
-                        // NOTE(emily): This Value is a real Song and we can just use it in place
-                        self.song_cache
-                            .write(
-                                id,
-                                self.resolve_sc_song(
-                                    serde_json::from_value(song)
-                                        .expect("Unable to deserialise sc Track"),
-                                )
-                                .await?,
-                            )
-                            .await
+            songs: self.resolve_sc_playlist_songs(sc_playlist.songs).await,
+            added_at: sc_playlist.added_at,
+            set_type: sc_playlist.set_type.filter(|t| !t.is_empty()),
+            is_album: sc_playlist.is_album,
+            track_count: sc_playlist.track_count,
+            duration: sc_playlist.duration,
+            release_date: sc_playlist.release_date,
+            created_at: sc_playlist.created_at,
+            description: sc_playlist.description.filter(|d| !d.is_empty()),
+        });
+
+        self.library.write().add_playlist(&playlist);
+        Ok(playlist)
+    }
+
+    /// Resolve the songs of a playlist, keeping them in playlist order.
+    /// Full songs are used as is, stubs are resolved in batches.
+    /// Songs that fail to resolve are kept as unavailable songs.
+    async fn resolve_sc_playlist_songs(&self, songs: Vec<sc::PlaylistSong>) -> Vec<Arc<Song>> {
+        let order: Vec<Id> = songs.iter().map(|song| song.id()).collect();
+
+        let mut full = vec![];
+        let mut stubs = vec![];
+        for song in songs {
+            match song {
+                sc::PlaylistSong::Full(song) => full.push(*song),
+                sc::PlaylistSong::Stub(stub) => {
+                    if !self.song_cache.contains(&stub.id).await {
+                        stubs.push(stub.id)
+                    }
+                }
+            }
+        }
+
+        // NOTE(emily): Full songs are real Songs and we can just use them in place
+        futures::stream::iter(full)
+            .for_each_concurrent(MAX_CONCURRENT_REQUESTS, |sc_song| async move {
+                let id = sc_song.object.id;
+                match self.resolve_sc_song(sc_song).await {
+                    Ok(song) => self.song_cache.write(id, song).await,
+                    Err(err) => warn!("Failed to resolve song {id}: {err}"),
+                }
+            })
+            .await;
+
+        futures::stream::iter(stubs.chunks(SONG_BATCH_SIZE))
+            .for_each_concurrent(MAX_CONCURRENT_REQUESTS, |ids| async move {
+                match self.soundcloud.songs_by_ids(ids).await {
+                    Ok(sc_songs) => {
+                        for sc_song in sc_songs {
+                            let id = sc_song.object.id;
+                            match self.resolve_sc_song(sc_song).await {
+                                Ok(song) => self.song_cache.write(id, song).await,
+                                Err(err) => warn!("Failed to resolve song {id}: {err}"),
+                            }
+                        }
                     }
-                    self.song(&id).await
+                    // Anything left over will get picked up individually below
+                    Err(err) => warn!("Failed to resolve batch of {} songs: {err}", ids.len()),
                 }
-            }))
+            })
+            .await;
+
+        // Everything should be cached by now, apart from songs that were missing from
+        // the batches which we try individually.
+        futures::stream::iter(order)
+            .map(|id| async move {
+                self.song(&id).await.unwrap_or_else(|err| {
+                    Arc::new(Song::unavailable(id, Availability::from_error(&err)))
+                })
+            })
+            .buffered(MAX_CONCURRENT_REQUESTS)
+            .collect()
             .await
-            .iter()
-            .filter_map(|x| x.as_ref().ok())
-            .cloned()
-            .collect(),
-        }))
+    }
+
+    pub async fn waveform(&self, song: &Song) -> Result<Arc<Waveform>> {
+        Ok(self
+            .waveform_cache
+            .get(&song.id, async {
+                let url = song
+                    .waveform_url
+                    .as_deref()
+                    .ok_or_else(|| eyre::eyre!("Song {} has no waveform", song.id))?;
+                Ok(Arc::new(self.soundcloud.waveform(url).await?.into()))
+            })
+            .await?)
+    }
+
+    /// Songs like this one, best first
+    pub async fn related(&self, id: &Id) -> Result<Arc<Vec<Arc<Song>>>> {
+        Ok(self
+            .related_cache
+            .get(id, async {
+                let mut songs = vec![];
+                for sc_song in self.soundcloud.related_songs(*id).await? {
+                    let song = self.resolve_sc_song(sc_song).await?;
+                    self.song_cache.write(song.id, song.clone()).await;
+                    songs.push(song);
+                }
+                Ok(Arc::new(songs))
+            })
+            .await?)
+    }
+
+    /// Comments on the song, in the order that they appear in it. Comments
+    /// that are not at a point in the song come last.
+    pub async fn song_comments(&self, id: &Id) -> Result<Arc<Vec<Comment>>> {
+        Ok(self
+            .comments_cache
+            .get(id, async {
+                let mut comments: Vec<Comment> = self
+                    .soundcloud
+                    .song_comments(*id)
+                    .await?
+                    .into_iter()
+                    .map(Comment::from)
+                    .collect();
+
+                comments.sort_by_key(|comment| comment.timestamp.unwrap_or(usize::MAX));
+                // NOTE(emily): Comments are fetched oldest first, so thin them out after
+                // sorting to keep some from all the way through the song
+                Ok(Arc::new(spread(comments, MAX_COMMENTS)))
+            })
+            .await?)
+    }
+
+    /// Playlists that the song is in
+    pub async fn song_playlists(&self, id: &Id) -> Result<Arc<Vec<PlaylistSummary>>> {
+        Ok(self
+            .song_playlists_cache
+            .get(id, async {
+                let summaries = self.soundcloud.song_playlists(*id).await?;
+                Ok(Arc::new(
+                    summaries
+                        .into_iter()
+                        .map(|summary| PlaylistSummary {
+                            id: summary.object.id,
+                            title: summary.title,
+                            username: summary.user.username,
+                            track_count: summary.track_count,
+                        })
+                        .collect(),
+                ))
+            })
+            .await?)
     }
 
     pub async fn playlist(&self, id: &Id) -> Result<Arc<Playlist>> {
@@ -248,62 +941,254 @@ impl Store {
             .await?)
     }
 
-    pub async fn image(&self, url: &str) -> Result<Arc<iced::widget::image::Handle>> {
-        Ok(self
-            .image_cache
-            .get(&url.to_owned(), async {
-                self.soundcloud
-                    .image(url)
-                    .await
-                    .map(|image| Arc::new(image))
+    pub async fn image(
+        &self,
+        url: &str,
+        size: ImageSize,
+    ) -> Result<Arc<iced::widget::image::Handle>> {
+        Self::fetch_image(&self.soundcloud, &self.image_cache, url, size).await
+    }
+
+    async fn fetch_image(
+        soundcloud: &SoundCloud,
+        image_cache: &Cache<(String, ImageSize), iced::widget::image::Handle>,
+        url: &str,
+        size: ImageSize,
+    ) -> Result<Arc<iced::widget::image::Handle>> {
+        Ok(image_cache
+            .get(&(url.to_owned(), size), async {
+                let bytes = soundcloud.image(&size.url(url)).await?;
+                // Decoding and resizing is slow, keep it away from everything else
+                let image =
+                    tokio::task::spawn_blocking(move || decode_image(bytes, size)).await??;
+                Ok(Arc::new(image))
             })
             .await?)
     }
 
+    /// An image that starts loading in the background once it is requested, see
+    /// Eventually::request. Failing to load the image just means that it never shows up.
+    pub fn lazy_image(
+        &self,
+        url: Option<&str>,
+        size: ImageSize,
+    ) -> Eventually<iced::widget::image::Handle> {
+        let Some(url) = url else {
+            return Eventually::never();
+        };
+
+        let url = url.to_owned();
+        let soundcloud = self.soundcloud.clone();
+        let image_cache = self.image_cache.clone();
+        let image_permits = self.image_permits.clone();
+        let images_loaded = self.images_loaded.clone();
+        let redraw_pending = self.images_redraw_pending.clone();
+        let runtime = self.runtime.clone();
+
+        Eventually::deferred(move |tx| {
+            runtime.spawn(async move {
+                let image = {
+                    let Ok(_permit) = image_permits.acquire().await else {
+                        return;
+                    };
+                    Self::fetch_image(&soundcloud, &image_cache, &url, size).await
+                };
+
+                let image = match image {
+                    Ok(image) => image,
+                    Err(err) => {
+                        warn!("Failed to load image {url}: {err}");
+                        return;
+                    }
+                };
+                // NOTE(emily): Nobody might care about this image any more
+                let _ = tx.send(Some(image));
+
+                // Whoever gets here first waits a little so that everything else that loads
+                // in the mean time is picked up by the same redraw
+                if !redraw_pending.swap(true, Ordering::AcqRel) {
+                    tokio::time::sleep(IMAGE_REDRAW_INTERVAL).await;
+                    redraw_pending.store(false, Ordering::Release);
+                    images_loaded.send_modify(|loaded| *loaded += 1);
+                }
+            });
+        })
+    }
+
+    /// Changes after lazy images finish loading
+    pub fn images_loaded(&self) -> watch::Receiver<usize> {
+        self.images_loaded.subscribe()
+    }
+
     pub async fn resolve_url(&self, url: &str) -> Result<Id> {
         self.soundcloud.url(url).await.map(|r| r.id)
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 enum _Eventually<T> {
-    NotAvailable(watch::Receiver<Arc<T>>),
+    NotAvailable(watch::Receiver<Option<Arc<T>>>),
     Available(Arc<T>),
+    Never,
 }
 
-impl<T> _Eventually<T> {
-    pub fn new(sender: &watch::Sender<Arc<T>>) -> Self {
-        Self::NotAvailable(sender.subscribe())
+impl<T> Clone for _Eventually<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::NotAvailable(rx) => Self::NotAvailable(rx.clone()),
+            Self::Available(v) => Self::Available(v.clone()),
+            Self::Never => Self::Never,
+        }
     }
 }
 
+/// Whatever produces the value of a deferred Eventually. Shared between clones so that
+/// it only ever starts once.
+#[derive(Clone, Default)]
+struct Start(Arc<Mutex<Option<Box<dyn FnOnce() + Send>>>>);
+
+impl std::fmt::Debug for Start {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let started = self.0.lock().is_none();
+        f.debug_struct("Start").field("started", &started).finish()
+    }
+}
+
+/// A value that might show up at some point in the future (e.g. artwork that is still
+/// downloading). Check for it with maybe() whenever it is needed.
 #[derive(Debug)]
-struct Eventually<T: Clone>(Mutex<_Eventually<T>>);
+pub struct Eventually<T>(Mutex<_Eventually<T>>, Start);
 
-impl<T: Clone> Clone for Eventually<T> {
+impl<T> Clone for Eventually<T> {
     fn clone(&self) -> Self {
         let inside = self.0.lock();
-        Self(Mutex::new(inside.clone()))
+        Self(Mutex::new(inside.clone()), self.1.clone())
+    }
+}
+
+impl<T> Default for Eventually<T> {
+    fn default() -> Self {
+        Self::never()
     }
 }
 
-impl<T: Clone> Eventually<T> {
+impl<T> Eventually<T> {
+    /// A value that will be available once something is sent on the returned sender
+    pub fn pending() -> (watch::Sender<Option<Arc<T>>>, Self) {
+        let (tx, rx) = watch::channel(None);
+        (
+            tx,
+            Self(
+                Mutex::new(_Eventually::NotAvailable(rx)),
+                Default::default(),
+            ),
+        )
+    }
+
+    /// A value that is only started on (by calling start with the sender) once someone
+    /// calls request()
+    pub fn deferred(start: impl FnOnce(watch::Sender<Option<Arc<T>>>) + Send + 'static) -> Self
+    where
+        T: Send + Sync + 'static,
+    {
+        let (tx, zelf) = Self::pending();
+        *zelf.1 .0.lock() = Some(Box::new(move || start(tx)));
+        zelf
+    }
+
+    pub fn ready(v: Arc<T>) -> Self {
+        Self(Mutex::new(_Eventually::Available(v)), Default::default())
+    }
+
+    /// A value that is never going to turn up
+    pub fn never() -> Self {
+        Self(Mutex::new(_Eventually::Never), Default::default())
+    }
+
+    /// Start producing the value if it is deferred and hasnt been started yet
+    pub fn request(&self) {
+        let start = self.1 .0.lock().take();
+        if let Some(start) = start {
+            start();
+        }
+    }
+
     pub fn maybe(&self) -> Option<Arc<T>> {
         let mut zelf = self.0.try_lock();
         match zelf.as_mut() {
             Some(garbage) => match &mut **garbage {
                 _Eventually::NotAvailable(rx) => {
-                    if let Ok(true) = rx.has_changed() {
-                        let v = rx.borrow_and_update().clone();
+                    let v = rx.borrow().clone();
+                    if let Some(v) = &v {
                         **garbage = _Eventually::Available(v.clone());
-                        Some(v)
-                    } else {
-                        None
                     }
+                    v
                 }
                 _Eventually::Available(v) => Some(v.clone()),
+                _Eventually::Never => None,
             },
             None => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spread_picks_from_all_the_way_through() {
+        assert_eq!(spread(vec![1, 2, 3], 5), [1, 2, 3]);
+        assert_eq!(spread((0..10).collect::<Vec<_>>(), 5), [0, 2, 4, 6, 8]);
+        assert_eq!(spread((0..10).collect::<Vec<_>>(), 4), [0, 2, 5, 7]);
+
+        let picked = spread((0..10_000).collect::<Vec<_>>(), 1000);
+        assert_eq!(picked.len(), 1000);
+        assert!(*picked.last().unwrap() >= 9_990);
+    }
+
+    #[test]
+    fn image_urls_point_at_a_big_enough_variant() {
+        let url = "https://i1.sndcdn.com/artworks-000-abc-large.jpg";
+        assert_eq!(
+            ImageSize::ListThumb.url(url),
+            "https://i1.sndcdn.com/artworks-000-abc-t300x300.jpg"
+        );
+        assert_eq!(ImageSize::Controls.url(url), url);
+        assert_eq!(
+            ImageSize::Hero.url(url),
+            "https://i1.sndcdn.com/artworks-000-abc-t500x500.jpg"
+        );
+    }
+
+    #[test]
+    fn collections_for_the_same_user_dont_collide() {
+        let collections = [
+            UserCollection::Likes,
+            UserCollection::Tracks,
+            UserCollection::Reposts,
+        ];
+        let keys: Vec<CollectionKey> = [1, 2]
+            .into_iter()
+            .flat_map(|id| {
+                collections
+                    .into_iter()
+                    .map(move |collection| CollectionKey::user(id, collection))
+            })
+            .collect();
+
+        for (i, a) in keys.iter().enumerate() {
+            for b in &keys[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+
+        // Nor with anything that isnt a users collection
+        let others = [
+            CollectionKey::Search("1".into()),
+            CollectionKey::Tag("1".into()),
+            CollectionKey::Chart(ChartKind::Top, "1".into()),
+        ];
+        assert!(keys.iter().all(|key| !others.contains(key)));
+    }
+}
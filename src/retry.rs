@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use backoff::{ExponentialBackoff, ExponentialBackoffBuilder};
+use log::warn;
+use once_cell::sync::Lazy;
+use reqwest::header;
+
+use crate::sc::api::ApiError;
+
+static POLICY: Lazy<RetryPolicy> = Lazy::new(RetryPolicy::default);
+
+/// Longest we are willing to wait when a server tells us to come back later
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: usize,
+    pub initial_interval: Duration,
+    pub max_interval: Duration,
+    pub multiplier: f64,
+    /// How much each interval is randomly spread by (0.0 is no jitter)
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_interval: Duration::from_millis(250),
+            max_interval: Duration::from_secs(8),
+            multiplier: 2.0,
+            jitter: 0.5,
+        }
+    }
+}
+
+/// The policy used by every request
+pub fn policy() -> &'static RetryPolicy {
+    &POLICY
+}
+
+/// What went wrong with a single attempt
+enum Failure {
+    /// We got a response, but the status says we should try again
+    Response(reqwest::Response),
+    Error(ApiError),
+}
+
+/// Parse a Retry-After header (only the delay-seconds form is supported)
+pub fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|secs| Duration::from_secs(secs).min(MAX_RETRY_AFTER))
+}
+
+impl RetryPolicy {
+    fn backoff(&self) -> ExponentialBackoff {
+        ExponentialBackoffBuilder::new()
+            .with_initial_interval(self.initial_interval)
+            .with_max_interval(self.max_interval)
+            .with_multiplier(self.multiplier)
+            .with_randomization_factor(self.jitter)
+            // NOTE(emily): We limit by attempts instead
+            .with_max_elapsed_time(None)
+            .build()
+    }
+
+    /// Send request, retrying whenever the ApiError for what went wrong is_transient.
+    /// If we run out of attempts on a bad status then that response is returned so that
+    /// the caller can decide what to do with it.
+    pub async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, ApiError> {
+        // NOTE(emily): Streaming bodies cant be cloned, so those only get one go
+        if request.try_clone().is_none() {
+            return Ok(request.send().await?);
+        }
+
+        let mut attempt = 0;
+
+        let result = backoff::future::retry_notify(
+            self.backoff(),
+            || {
+                attempt += 1;
+                let last_attempt = attempt >= self.max_attempts;
+                let request = request.try_clone().expect("Request was cloneable");
+
+                async move {
+                    let failure = match request.send().await.map_err(ApiError::from) {
+                        Ok(response) => match ApiError::from_response(&response) {
+                            Some(err) if err.is_transient() => {
+                                let retry_after = retry_after(&response);
+                                let failure = Failure::Response(response);
+                                if let (Some(retry_after), false) = (retry_after, last_attempt) {
+                                    return Err(backoff::Error::retry_after(failure, retry_after));
+                                }
+                                failure
+                            }
+                            _ => return Ok(response),
+                        },
+                        Err(err) if err.is_transient() => Failure::Error(err),
+                        Err(err) => return Err(backoff::Error::permanent(Failure::Error(err))),
+                    };
+
+                    if last_attempt {
+                        Err(backoff::Error::permanent(failure))
+                    } else {
+                        Err(backoff::Error::transient(failure))
+                    }
+                }
+            },
+            |failure: Failure, wait: Duration| match failure {
+                Failure::Response(response) => warn!(
+                    "{} returned {}, retrying in {:?}",
+                    response.url(),
+                    response.status(),
+                    wait
+                ),
+                Failure::Error(err) => warn!("Request failed ({err}), retrying in {:?}", wait),
+            },
+        )
+        .await;
+
+        match result {
+            Ok(response) => Ok(response),
+            Err(Failure::Response(response)) => Ok(response),
+            Err(Failure::Error(err)) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Hang up without responding
+    const DROP: &str = "";
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_interval: Duration::from_millis(1),
+            max_interval: Duration::from_millis(5),
+            multiplier: 2.0,
+            jitter: 0.0,
+        }
+    }
+
+    /// Serve one connection per entry in replies, which are a status line plus any headers
+    /// (or DROP). Once they run out everything gets a 200. Returns the url and how many
+    /// requests were made.
+    async fn serve(replies: Vec<&'static str>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            let mut replies = replies.into_iter();
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+
+                let mut request = vec![];
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let reply = replies.next().unwrap_or("200 OK");
+                if reply == DROP {
+                    continue;
+                }
+
+                let response =
+                    format!("HTTP/1.1 {reply}\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_until_success() {
+        let (url, requests) =
+            serve(vec!["503 Service Unavailable", "500 Internal Server Error"]).await;
+
+        let response = fast_policy()
+            .send(reqwest::Client::new().get(&url))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn returns_the_last_response_when_out_of_attempts() {
+        let (url, requests) = serve(vec!["429 Too Many Requests"; 5]).await;
+
+        let response = fast_policy()
+            .send(reqwest::Client::new().get(&url))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn honours_retry_after() {
+        let (url, requests) = serve(vec!["503 Service Unavailable\r\nRetry-After: 1"]).await;
+
+        let start = std::time::Instant::now();
+        let response = fast_policy()
+            .send(reqwest::Client::new().get(&url))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let (url, requests) = serve(vec!["404 Not Found"]).await;
+
+        let response = fast_policy()
+            .send(reqwest::Client::new().get(&url))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_dropped_connections() {
+        let (url, requests) = serve(vec![DROP, DROP]).await;
+
+        let response = fast_policy()
+            .send(reqwest::Client::new().get(&url))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+}
@@ -1,8 +1,10 @@
-pub use api::model::{Media, Object, Playlist, Song, User};
+pub use api::model::{
+    ChartKind, Comment, Media, Object, Playlist, PlaylistSong, PlaylistSummary, Song, User,
+};
 use eyre::Result;
 
 pub mod api {
-    use log::info;
+    use log::{info, warn};
 
     use serde::Deserialize;
 
@@ -76,6 +78,9 @@ pub mod api {
         pub struct Transcoding {
             pub url: String,
             pub format: Format,
+            /// Whether this is only a 30 second preview of the song
+            #[serde(default)]
+            pub snipped: bool,
         }
 
         assert_impl_all!(Transcoding: Send, Sync);
@@ -93,6 +98,23 @@ pub mod api {
             pub username: String,
             #[serde(rename = "avatar_url")]
             pub avatar: Option<String>,
+            // NOTE(emily): Users attached to songs and playlists leave most of these out
+            #[serde(default)]
+            pub followers_count: Option<u64>,
+            #[serde(default)]
+            pub followings_count: Option<u64>,
+            #[serde(default)]
+            pub description: Option<String>,
+            #[serde(default)]
+            pub city: Option<String>,
+            #[serde(default)]
+            pub country_code: Option<String>,
+            #[serde(default)]
+            pub verified: bool,
+            #[serde(default)]
+            pub track_count: Option<u64>,
+            #[serde(default)]
+            pub likes_count: Option<u64>,
         }
 
         impl Objectable for User {
@@ -101,6 +123,20 @@ pub mod api {
             }
         }
 
+        /// What we are allowed to do with a song
+        #[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+        #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+        pub enum Policy {
+            #[default]
+            Allow,
+            Monetize,
+            /// Only a preview can be played (e.g. Go+ songs)
+            Snip,
+            Block,
+            #[serde(other)]
+            Unknown,
+        }
+
         #[derive(Deserialize, Serialize, Debug, Default, Clone)]
         pub struct Song {
             #[serde(flatten)]
@@ -113,13 +149,123 @@ pub mod api {
             pub media: Media,
             // This is in milliseconds
             pub full_duration: usize,
+            #[serde(default)]
+            pub policy: Policy,
+            pub monetization_model: Option<String>,
+            /// When the song was uploaded
+            #[serde(default)]
+            pub created_at: Option<String>,
+            #[serde(default)]
+            pub playback_count: Option<u64>,
+            #[serde(default)]
+            pub likes_count: Option<u64>,
+            #[serde(default)]
+            pub genre: Option<String>,
+            #[serde(default)]
+            pub description: Option<String>,
+            /// Space separated, tags with spaces in them are quoted
+            #[serde(default)]
+            pub tag_list: Option<String>,
+            #[serde(default)]
+            pub release_date: Option<String>,
+            #[serde(default)]
+            pub label_name: Option<String>,
+            #[serde(default)]
+            pub reposts_count: Option<u64>,
+            #[serde(default)]
+            pub comment_count: Option<u64>,
+            #[serde(default)]
+            pub license: Option<String>,
+            #[serde(default)]
+            pub purchase_url: Option<String>,
+            #[serde(default)]
+            pub waveform_url: Option<String>,
+        }
+
+        #[derive(Deserialize, Serialize, Debug, Default, Clone)]
+        pub struct Waveform {
+            pub width: usize,
+            /// Largest value that a sample can be
+            pub height: u32,
+            pub samples: Vec<u32>,
+        }
+
+        /// Just enough of a playlist to link to it
+        #[derive(Deserialize, Serialize, Debug, Default, Clone)]
+        pub struct PlaylistSummary {
+            #[serde(flatten)]
+            pub object: Object,
+
+            pub title: String,
+            pub user: User,
+            #[serde(default)]
+            pub track_count: Option<usize>,
+        }
+
+        /// A comment on a song, possibly at a point in it
+        #[derive(Deserialize, Serialize, Debug, Default, Clone)]
+        pub struct Comment {
+            #[serde(flatten)]
+            pub object: Object,
+
+            pub body: String,
+            pub user: User,
+            /// Milliseconds into the song that the comment was left at
+            #[serde(default)]
+            pub timestamp: Option<usize>,
+            #[serde(default)]
+            pub created_at: Option<String>,
+        }
+
+        /// Which of SoundCloud's charts
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum ChartKind {
+            /// Most played
+            Top,
+            /// New & hot
+            Trending,
+        }
+
+        impl ChartKind {
+            pub fn param(self) -> &'static str {
+                match self {
+                    ChartKind::Top => "top",
+                    ChartKind::Trending => "trending",
+                }
+            }
+
+            pub fn title(self) -> &'static str {
+                match self {
+                    ChartKind::Top => "Top 50",
+                    ChartKind::Trending => "New & hot",
+                }
+            }
         }
 
+        /// A song that we only know the id of
         #[derive(Deserialize, Serialize, Debug, Default, Clone)]
         pub struct BlackboxSong {
             pub id: i64,
         }
 
+        /// Songs inside of playlists are only fully filled in for the first few,
+        /// the rest are just stubs that need resolving.
+        #[derive(Deserialize, Serialize, Debug, Clone)]
+        #[serde(untagged)]
+        pub enum PlaylistSong {
+            Full(Box<Song>),
+            Stub(BlackboxSong),
+        }
+
+        impl PlaylistSong {
+            pub fn id(&self) -> Id {
+                match self {
+                    PlaylistSong::Full(song) => song.object.id,
+                    PlaylistSong::Stub(stub) => stub.id,
+                }
+            }
+        }
+
         impl Objectable for Song {
             fn object(&self) -> &Object {
                 return &self.object;
@@ -135,8 +281,28 @@ pub mod api {
             pub artwork: Option<String>,
             pub user: Object,
             #[serde(rename = "tracks")]
-            pub songs: Vec<serde_json::Value>,
+            pub songs: Vec<PlaylistSong>,
             pub title: String,
+            /// When each song was added to this collection (e.g. liked), in the same order as songs.
+            /// Only filled in for the collections that we build ourselves.
+            #[serde(skip)]
+            pub added_at: Vec<Option<String>>,
+            /// e.g. album, ep, single. Empty for plain playlists.
+            #[serde(default)]
+            pub set_type: Option<String>,
+            #[serde(default)]
+            pub is_album: bool,
+            #[serde(default)]
+            pub track_count: Option<usize>,
+            /// In milliseconds
+            #[serde(default)]
+            pub duration: Option<usize>,
+            #[serde(default)]
+            pub release_date: Option<String>,
+            #[serde(default)]
+            pub created_at: Option<String>,
+            #[serde(default)]
+            pub description: Option<String>,
         }
 
         impl Objectable for Playlist {
@@ -151,25 +317,166 @@ pub mod api {
         }
     }
 
-    use std::sync::atomic::AtomicI64;
+    use std::{fmt, sync::atomic::AtomicI64, time::Duration};
 
-    use eyre::{eyre, Result, WrapErr};
     use lazy_static::lazy_static;
-    use reqwest::header;
+    use reqwest::{header, StatusCode};
 
     use self::model::Object;
+    use crate::retry;
+
+    /// Errors that can come back from talking to the SoundCloud API
+    #[derive(Debug)]
+    pub enum ApiError {
+        Unauthorized,
+        /// Usually means that the object is not available in our region
+        Forbidden,
+        NotFound,
+        RateLimited {
+            retry_after: Option<Duration>,
+        },
+        Server {
+            status: StatusCode,
+        },
+        /// Any other status that we did not expect
+        Status {
+            status: StatusCode,
+            body: String,
+        },
+        Decode {
+            type_name: &'static str,
+            /// The start of the body that failed to decode
+            body: String,
+            source: serde_json::Error,
+        },
+        Network(reqwest::Error),
+    }
+
+    impl ApiError {
+        /// The error for an unsuccessful response, leaving the body of Status empty
+        pub fn from_response(response: &reqwest::Response) -> Option<Self> {
+            let status = response.status();
+            if status.is_success() {
+                return None;
+            }
+
+            Some(match status {
+                StatusCode::UNAUTHORIZED => ApiError::Unauthorized,
+                StatusCode::FORBIDDEN => ApiError::Forbidden,
+                StatusCode::NOT_FOUND => ApiError::NotFound,
+                StatusCode::TOO_MANY_REQUESTS => ApiError::RateLimited {
+                    retry_after: retry::retry_after(response),
+                },
+                status if status.is_server_error() => ApiError::Server { status },
+                status => ApiError::Status {
+                    status,
+                    body: String::new(),
+                },
+            })
+        }
+
+        /// Whether trying the same request again later might work
+        pub fn is_transient(&self) -> bool {
+            match self {
+                ApiError::RateLimited { .. } | ApiError::Server { .. } => true,
+                ApiError::Status { status, .. } => *status == StatusCode::REQUEST_TIMEOUT,
+                // NOTE(emily): Request and body errors are usually the connection going away
+                // part way through
+                ApiError::Network(err) => {
+                    err.is_timeout() || err.is_connect() || err.is_request() || err.is_body()
+                }
+                _ => false,
+            }
+        }
+    }
+
+    impl fmt::Display for ApiError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ApiError::Unauthorized => write!(f, "SoundCloud rejected our client id"),
+                ApiError::Forbidden => write!(f, "Not available in your region"),
+                ApiError::NotFound => {
+                    write!(f, "Not found (it may have been deleted or made private)")
+                }
+                ApiError::RateLimited {
+                    retry_after: Some(retry_after),
+                } => write!(
+                    f,
+                    "Rate limited by SoundCloud, try again in {}s",
+                    retry_after.as_secs()
+                ),
+                ApiError::RateLimited { retry_after: None } => {
+                    write!(f, "Rate limited by SoundCloud, try again later")
+                }
+                ApiError::Server { status } => {
+                    write!(f, "SoundCloud is having problems ({status})")
+                }
+                ApiError::Status { status, body } => {
+                    write!(f, "Unexpected response {status} (body was {body})")
+                }
+                ApiError::Decode {
+                    type_name, body, ..
+                } => write!(f, "Failed to decode {type_name} (body was {body})"),
+                ApiError::Network(err) => write!(f, "Network error: {err}"),
+            }
+        }
+    }
+
+    impl std::error::Error for ApiError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                ApiError::Decode { source, .. } => Some(source),
+                ApiError::Network(err) => Some(err),
+                _ => None,
+            }
+        }
+    }
+
+    impl From<reqwest::Error> for ApiError {
+        fn from(err: reqwest::Error) -> Self {
+            ApiError::Network(err)
+        }
+    }
+
+    pub type Result<T, E = ApiError> = std::result::Result<T, E>;
+
+    fn excerpt(text: &str) -> String {
+        const EXCERPT_LEN: usize = 200;
+
+        match text.char_indices().nth(EXCERPT_LEN) {
+            Some((end, _)) => format!("{}...", &text[..end]),
+            None => text.to_owned(),
+        }
+    }
+
+    /// Turn an unsuccessful response into the matching ApiError
+    async fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+        match ApiError::from_response(&response) {
+            None => Ok(response),
+            Some(ApiError::Status { status, .. }) => Err(ApiError::Status {
+                status,
+                body: excerpt(&response.text().await.unwrap_or_default()),
+            }),
+            Some(err) => Err(err),
+        }
+    }
+
+    fn decode<T: for<'de> serde::Deserialize<'de>>(text: &str) -> Result<T> {
+        serde_json::from_str(text).map_err(|source| ApiError::Decode {
+            type_name: std::any::type_name::<T>(),
+            body: excerpt(text),
+            source,
+        })
+    }
 
     const API_ORIGIN: &str = "https://api-widget.soundcloud.com";
     const CLIENT_ID: &str = env!("STRATUS_CLIENT_ID");
-    const USER_AGENT: &str =
-        "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:88.0) Gecko/20100101 Firefox/88.0";
 
     lazy_static! {
         static ref COMMON_HEADERS: header::HeaderMap = {
             let mut headers = header::HeaderMap::new();
             headers.insert(header::HOST, "api-widget.soundcloud.com".parse().unwrap());
             headers.insert(header::ORIGIN, "w.soundcloud.com".parse().unwrap());
-            headers.insert(header::USER_AGENT, USER_AGENT.parse().unwrap());
 
             headers
         };
@@ -180,47 +487,53 @@ pub mod api {
         // let headers = COMMON_HEADERS.clone();
         // let params = COMMON_PARAMS.clone();
 
-        let response = client
-            .get(url)
-            // .query(&params)
-            // .headers(headers)
-            .send()
-            .await?;
+        let response = check_status(retry::policy().send(client.get(url)).await?).await?;
 
         Ok(response.bytes().await?.to_vec())
     }
 
-    pub async fn hls_playlist(url: &str) -> Result<String> {
-        let client = reqwest::Client::new();
+    /// Amplitude samples from a songs waveform_url
+    pub async fn waveform(client: &reqwest::Client, url: &str) -> Result<model::Waveform> {
+        // NOTE(emily): Older songs point at a png of the waveform, the same samples are
+        // available as json right next to it
+        let url = match url.strip_suffix(".png") {
+            Some(base) => format!("{base}.json"),
+            None => url.to_string(),
+        };
 
+        let response = check_status(retry::policy().send(client.get(&url)).await?).await?;
+        decode(&response.text().await?)
+    }
+
+    pub async fn hls_playlist(client: &reqwest::Client, url: &str) -> Result<String> {
         let headers = COMMON_HEADERS.clone();
         let params = COMMON_PARAMS.clone();
 
-        let response = client
-            .get(url)
-            .query(&params)
-            .headers(headers)
-            .send()
+        let response = retry::policy()
+            .send(client.get(url).query(&params).headers(headers))
             .await?;
 
-        let text = response.text().await?;
+        let text = check_status(response).await?.text().await?;
 
-        let playlist: model::HlsPlaylist = serde_json::from_str(&text).wrap_err(format!(
-            "Failed to decode HLS Playlist from API (text was {})",
-            &text
-        ))?;
+        let playlist: model::HlsPlaylist = decode(&text)?;
 
         // now get the actual m3u8 from the response object
         let mut headers = header::HeaderMap::new();
-        headers.insert(header::HOST, "cf-hls-media.sndcdn.com".parse()?);
+        headers.insert(
+            header::HOST,
+            header::HeaderValue::from_static("cf-hls-media.sndcdn.com"),
+        );
         headers.insert(
             header::ACCEPT,
-            "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8"
-                .parse()?,
+            header::HeaderValue::from_static(
+                "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8",
+            ),
         );
 
-        let response = client.get(playlist.url).headers(headers).send().await?;
-        let playlist = response.text().await?;
+        let response = retry::policy()
+            .send(client.get(playlist.url).headers(headers))
+            .await?;
+        let playlist = check_status(response).await?.text().await?;
 
         Ok(playlist)
     }
@@ -252,6 +565,22 @@ pub mod api {
                 },
             }
         }
+
+        /// Endpoint for a full url that the API gave us, e.g. a next_href
+        pub fn from_href(href: &str) -> Option<Endpoint> {
+            let url = reqwest::Url::parse(href).ok()?;
+
+            Some(Endpoint {
+                endpoint: url.path().trim_start_matches('/').into(),
+                params: Some(
+                    url.query_pairs()
+                        // NOTE(emily): We always add our own client_id
+                        .filter(|(key, _)| key != "client_id")
+                        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                        .collect(),
+                ),
+            })
+        }
     }
 
     pub async fn object<T: for<'de> serde::Deserialize<'de>>(
@@ -268,21 +597,53 @@ pub mod api {
 
         info!("GETting {}", final_endpoint);
 
-        let response = client
-            .get(final_endpoint)
-            .query(&params)
-            .headers(headers)
-            .send()
+        let response = retry::policy()
+            .send(client.get(final_endpoint).query(&params).headers(headers))
             .await?;
 
-        let text = response.text().await?;
+        let text = check_status(response).await?.text().await?;
         // let v: serde_json::Value = serde_json::from_str(&text)?;
         // let text = serde_json::to_string_pretty(&v)?;
 
-        let object = serde_json::from_str(&text)
-            .map_err(|e| eyre!(e).wrap_err(eyre!("T was {}", std::any::type_name::<T>(),)))?;
+        decode(&text)
+    }
 
-        Ok(object)
+    /// One page of a paginated collection
+    #[derive(Deserialize)]
+    struct Page<T> {
+        collection: Vec<T>,
+        next_href: Option<String>,
+    }
+
+    /// Follow next_href until there are no more pages, or we have at least max items
+    pub async fn paginated<T: for<'de> serde::Deserialize<'de>>(
+        client: &reqwest::Client,
+        mut endpoint: Endpoint,
+        max: usize,
+    ) -> Result<Vec<T>> {
+        let mut items = vec![];
+
+        loop {
+            let page: Page<T> = object(client, endpoint).await?;
+            items.extend(page.collection);
+
+            let Some(next_href) = page.next_href else {
+                break;
+            };
+            if items.len() >= max {
+                break;
+            }
+
+            match Endpoint::from_href(&next_href) {
+                Some(next) => endpoint = next,
+                None => {
+                    warn!("Could not follow next_href {next_href:?}");
+                    break;
+                }
+            }
+        }
+
+        Ok(items)
     }
 
     fn next_fake_id() -> i64 {
@@ -302,7 +663,8 @@ pub mod api {
         pub async fn likes(&self, client: &reqwest::Client) -> Result<model::Playlist> {
             #[derive(Deserialize)]
             struct Like {
-                track: serde_json::Value,
+                track: model::PlaylistSong,
+                created_at: Option<String>,
             }
 
             #[derive(Deserialize)]
@@ -329,8 +691,14 @@ pub mod api {
                 },
                 artwork: self.avatar.clone(),
                 user: self.object.clone(),
+                added_at: likes
+                    .collection
+                    .iter()
+                    .map(|x| x.created_at.clone())
+                    .collect(),
                 songs: likes.collection.into_iter().map(|x| x.track).collect(),
                 title: format!("Liked by {}", self.username),
+                ..Default::default()
             })
         }
 
@@ -343,7 +711,7 @@ pub mod api {
 
             #[derive(Deserialize)]
             struct Songs {
-                collection: Vec<serde_json::Value>,
+                collection: Vec<model::PlaylistSong>,
             }
 
             let songs: Songs = object(client, endpoint).await?;
@@ -357,10 +725,219 @@ pub mod api {
                 },
                 artwork: self.avatar.clone(),
                 user: self.object.clone(),
-                songs: songs.collection.into_iter().map(|x| x).collect(),
+                // NOTE(emily): A users own songs were added when they were uploaded
+                added_at: songs
+                    .collection
+                    .iter()
+                    .map(|song| match song {
+                        model::PlaylistSong::Full(song) => song.created_at.clone(),
+                        model::PlaylistSong::Stub(_) => None,
+                    })
+                    .collect(),
+                songs: songs.collection,
                 title: format!("Tracks by {}", self.username),
+                ..Default::default()
             })
         }
+
+        pub async fn reposts(&self, client: &reqwest::Client) -> Result<model::Playlist> {
+            #[derive(Deserialize)]
+            struct Repost {
+                // NOTE(emily): Playlist reposts do not have a track
+                track: Option<model::PlaylistSong>,
+                created_at: Option<String>,
+            }
+
+            #[derive(Deserialize)]
+            struct Reposts {
+                collection: Vec<Repost>,
+            }
+
+            let endpoint = Endpoint {
+                endpoint: format!("stream/users/{}/reposts", self.object.id),
+                params: Some(vec![("limit".into(), "8000".into())]),
+            };
+
+            let reposts: Reposts = object(client, endpoint).await?;
+            let id = next_fake_id();
+
+            Ok(model::Playlist {
+                object: Object {
+                    id,
+                    kind: "reposts".into(),
+                    uri: None,
+                    url: None,
+                },
+                artwork: self.avatar.clone(),
+                user: self.object.clone(),
+                added_at: reposts
+                    .collection
+                    .iter()
+                    .filter(|x| x.track.is_some())
+                    .map(|x| x.created_at.clone())
+                    .collect(),
+                songs: reposts
+                    .collection
+                    .into_iter()
+                    .filter_map(|x| x.track)
+                    .collect(),
+                title: format!("Reposted by {}", self.username),
+                ..Default::default()
+            })
+        }
+
+        /// Users that follow this user
+        pub async fn followers(
+            client: &reqwest::Client,
+            id: model::Id,
+            max: usize,
+        ) -> Result<Vec<model::User>> {
+            let endpoint = Endpoint {
+                endpoint: format!("users/{}/followers", id),
+                params: Some(vec![("limit".into(), "200".into())]),
+            };
+
+            paginated(client, endpoint, max).await
+        }
+
+        /// Users that this user follows
+        pub async fn followings(
+            client: &reqwest::Client,
+            id: model::Id,
+            max: usize,
+        ) -> Result<Vec<model::User>> {
+            let endpoint = Endpoint {
+                endpoint: format!("users/{}/followings", id),
+                params: Some(vec![("limit".into(), "200".into())]),
+            };
+
+            paginated(client, endpoint, max).await
+        }
+
+        pub async fn playlists(&self, client: &reqwest::Client) -> Result<Vec<model::Playlist>> {
+            #[derive(Deserialize)]
+            struct Playlists {
+                collection: Vec<model::Playlist>,
+            }
+
+            let endpoint = Endpoint {
+                endpoint: format!("users/{}/playlists", self.object.id),
+                params: Some(vec![("limit".into(), "200".into())]),
+            };
+
+            let playlists: Playlists = object(client, endpoint).await?;
+            Ok(playlists.collection)
+        }
+    }
+
+    pub async fn search(client: &reqwest::Client, query: &str) -> Result<model::Playlist> {
+        #[derive(Deserialize)]
+        struct Songs {
+            collection: Vec<model::PlaylistSong>,
+        }
+
+        let endpoint = Endpoint {
+            endpoint: "search/tracks".into(),
+            params: Some(vec![
+                ("q".into(), query.into()),
+                ("limit".into(), "200".into()),
+            ]),
+        };
+
+        let songs: Songs = object(client, endpoint).await?;
+        let id = next_fake_id();
+
+        Ok(model::Playlist {
+            object: Object {
+                id,
+                kind: "search".into(),
+                uri: None,
+                url: None,
+            },
+            artwork: None,
+            user: Object::default(),
+            songs: songs.collection,
+            added_at: vec![],
+            title: format!("Results for \"{}\"", query),
+            ..Default::default()
+        })
+    }
+
+    /// Chart for genre, e.g. `electronic` or `all-music`
+    pub async fn chart(
+        client: &reqwest::Client,
+        kind: model::ChartKind,
+        genre: &str,
+    ) -> Result<model::Playlist> {
+        #[derive(Deserialize)]
+        struct Entry {
+            track: model::PlaylistSong,
+        }
+
+        #[derive(Deserialize)]
+        struct Chart {
+            collection: Vec<Entry>,
+        }
+
+        let endpoint = Endpoint {
+            endpoint: "charts".into(),
+            params: Some(vec![
+                ("kind".into(), kind.param().into()),
+                ("genre".into(), format!("soundcloud:genres:{genre}")),
+                ("limit".into(), "50".into()),
+            ]),
+        };
+
+        let chart: Chart = object(client, endpoint).await?;
+        let id = next_fake_id();
+
+        Ok(model::Playlist {
+            object: Object {
+                id,
+                kind: "chart".into(),
+                uri: None,
+                url: None,
+            },
+            artwork: None,
+            user: Object::default(),
+            songs: chart.collection.into_iter().map(|x| x.track).collect(),
+            title: format!("{} ({genre})", kind.title()),
+            ..Default::default()
+        })
+    }
+
+    /// Songs tagged with (or in the genre) tag
+    pub async fn tag(client: &reqwest::Client, tag: &str) -> Result<model::Playlist> {
+        #[derive(Deserialize)]
+        struct Songs {
+            collection: Vec<model::PlaylistSong>,
+        }
+
+        let endpoint = Endpoint {
+            endpoint: "search/tracks".into(),
+            params: Some(vec![
+                ("q".into(), "*".into()),
+                ("filter.genre_or_tag".into(), tag.into()),
+                ("limit".into(), "200".into()),
+            ]),
+        };
+
+        let songs: Songs = object(client, endpoint).await?;
+        let id = next_fake_id();
+
+        Ok(model::Playlist {
+            object: Object {
+                id,
+                kind: "tag".into(),
+                uri: None,
+                url: None,
+            },
+            artwork: None,
+            user: Object::default(),
+            songs: songs.collection,
+            title: format!("Tagged #{tag}"),
+            ..Default::default()
+        })
     }
 
     impl model::Song {
@@ -371,6 +948,81 @@ pub mod api {
             )
             .await
         }
+
+        /// Playlists that this song is in (not including albums)
+        pub async fn playlists(
+            client: &reqwest::Client,
+            id: model::Id,
+        ) -> Result<Vec<model::PlaylistSummary>> {
+            #[derive(Deserialize)]
+            struct Playlists {
+                collection: Vec<model::PlaylistSummary>,
+            }
+
+            let endpoint = Endpoint {
+                endpoint: format!("tracks/{}/playlists_without_albums", id),
+                params: Some(vec![("limit".into(), "20".into())]),
+            };
+
+            let playlists: Playlists = object(client, endpoint).await?;
+            Ok(playlists.collection)
+        }
+
+        /// Songs that SoundCloud thinks are like this one
+        pub async fn related(client: &reqwest::Client, id: model::Id) -> Result<Vec<Self>> {
+            #[derive(Deserialize)]
+            struct Related {
+                collection: Vec<model::Song>,
+            }
+
+            let endpoint = Endpoint {
+                endpoint: format!("tracks/{}/related", id),
+                params: Some(vec![("limit".into(), "20".into())]),
+            };
+
+            let related: Related = object(client, endpoint).await?;
+            Ok(related.collection)
+        }
+
+        /// Every comment on a song, oldest first
+        pub async fn comments(
+            client: &reqwest::Client,
+            id: model::Id,
+        ) -> Result<Vec<model::Comment>> {
+            let endpoint = Endpoint {
+                endpoint: format!("tracks/{}/comments", id),
+                params: Some(vec![
+                    ("threaded".into(), "0".into()),
+                    ("filter_replies".into(), "0".into()),
+                    ("sort".into(), "oldest".into()),
+                    ("limit".into(), "200".into()),
+                ]),
+            };
+
+            paginated(client, endpoint, usize::MAX).await
+        }
+
+        /// Resolve many songs in one request. Songs that are not available are
+        /// left out of the result, rather than failing the whole request.
+        pub async fn resolve_many(
+            client: &reqwest::Client,
+            ids: &[model::Id],
+        ) -> Result<Vec<Self>> {
+            let ids = ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+
+            object(
+                client,
+                Endpoint {
+                    endpoint: "tracks".into(),
+                    params: Some(vec![("ids".into(), ids)]),
+                },
+            )
+            .await
+        }
     }
 
     impl model::Playlist {
@@ -384,8 +1036,8 @@ pub mod api {
     }
 
     impl model::Transcoding {
-        pub async fn resolve(&self) -> Result<String> {
-            Ok(hls_playlist(&self.url).await?)
+        pub async fn resolve(&self, client: &reqwest::Client) -> Result<String> {
+            Ok(hls_playlist(client, &self.url).await?)
         }
     }
 }
@@ -424,16 +1076,34 @@ pub struct SoundCloud {
 }
 
 impl SoundCloud {
-    pub fn new() -> Self {
-        Self {
-            client: Default::default(),
-        }
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
     }
 
     pub async fn song(&self, id: Id<'_>) -> Result<Song> {
         Ok(Song::resolve(&self.client, id).await?)
     }
 
+    pub async fn songs_by_ids(&self, ids: &[api::model::Id]) -> Result<Vec<Song>> {
+        Ok(Song::resolve_many(&self.client, ids).await?)
+    }
+
+    pub async fn song_playlists(&self, id: api::model::Id) -> Result<Vec<PlaylistSummary>> {
+        Ok(Song::playlists(&self.client, id).await?)
+    }
+
+    pub async fn related_songs(&self, id: api::model::Id) -> Result<Vec<Song>> {
+        Ok(Song::related(&self.client, id).await?)
+    }
+
+    pub async fn song_comments(&self, id: api::model::Id) -> Result<Vec<api::model::Comment>> {
+        Ok(Song::comments(&self.client, id).await?)
+    }
+
     pub async fn user(&self, id: Id<'_>) -> Result<User> {
         Ok(User::resolve(&self.client, id).await?)
     }
@@ -442,9 +1112,12 @@ impl SoundCloud {
         Ok(Playlist::resolve(&self.client, id).await?)
     }
 
-    pub async fn image(&self, url: &str) -> Result<iced::widget::image::Handle> {
-        let image = api::image(&self.client, &url.replace("-large", "-t120x120")).await?;
-        Ok(iced::widget::image::Handle::from_memory(image))
+    pub async fn image(&self, url: &str) -> Result<Vec<u8>> {
+        Ok(api::image(&self.client, url).await?)
+    }
+
+    pub async fn waveform(&self, url: &str) -> Result<api::model::Waveform> {
+        Ok(api::waveform(&self.client, url).await?)
     }
 
     pub async fn likes(&self, id: Id<'_>) -> Result<Playlist> {
@@ -457,12 +1130,42 @@ impl SoundCloud {
         Ok(user.songs(&self.client).await?)
     }
 
+    pub async fn reposts(&self, id: Id<'_>) -> Result<Playlist> {
+        let user = self.user(id).await?;
+        Ok(user.reposts(&self.client).await?)
+    }
+
+    pub async fn followers(&self, id: api::model::Id, max: usize) -> Result<Vec<User>> {
+        Ok(User::followers(&self.client, id, max).await?)
+    }
+
+    pub async fn followings(&self, id: api::model::Id, max: usize) -> Result<Vec<User>> {
+        Ok(User::followings(&self.client, id, max).await?)
+    }
+
+    pub async fn user_playlists(&self, id: Id<'_>) -> Result<Vec<Playlist>> {
+        let user = self.user(id).await?;
+        Ok(user.playlists(&self.client).await?)
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Playlist> {
+        Ok(api::search(&self.client, query).await?)
+    }
+
+    pub async fn chart(&self, kind: ChartKind, genre: &str) -> Result<Playlist> {
+        Ok(api::chart(&self.client, kind, genre).await?)
+    }
+
+    pub async fn tag(&self, tag: &str) -> Result<Playlist> {
+        Ok(api::tag(&self.client, tag).await?)
+    }
+
     pub async fn url(&self, url: &str) -> Result<Object> {
-        api::object(
+        Ok(api::object(
             &self.client,
             api::Endpoint::from_id(Id::Url(url), |x| format!("{x}"), None),
         )
-        .await
+        .await?)
     }
 
     pub fn frame() {}
@@ -1,10 +1,14 @@
 use audio::HlsPlayer;
 use futures::stream::BoxStream;
 
-use crate::downloader;
-
-use std::collections::{HashMap, VecDeque};
-use std::sync::Arc;
+use crate::{downloader, http, library};
+
+use std::collections::VecDeque;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Duration;
 use tokio::sync::watch;
 
 use iced::widget;
@@ -13,15 +17,26 @@ use iced::{Application, Element};
 use log::{info, warn};
 
 use super::controls::ControlsElement;
+use super::discover_page::DiscoverPage;
 use super::playlist_page::PlaylistPage;
-use super::song_list::Display;
-use super::user_page::UserPage;
+use super::search_page::SearchPage;
+use super::song_list::{Display, SongList, SortColumn};
+use super::track_page::{TrackPage, TrackTab};
+use super::user_page::{TabLoad, UserPage, UserTab};
 use crate::model::{self, Store};
 
+/// How long the library search box has to be left alone before searching
+const LIBRARY_SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
+/// How often the cache metrics are written to the log
+const CACHE_METRICS_INTERVAL: Duration = Duration::from_secs(60);
+
 enum Page {
     Main,
     Playlist(PlaylistPage),
     User(UserPage),
+    Search(SearchPage),
+    Track(TrackPage),
+    Discover(DiscoverPage),
 }
 
 impl Default for Page {
@@ -37,14 +52,18 @@ pub struct App {
     store: Arc<model::Store>,
 
     player: Arc<audio::HlsPlayer>,
+    downloader: Arc<downloader::Downloader>,
     controls: ControlsElement,
+    /// Text in the global library search box
+    library_query: String,
+    /// Set to stop the most recent library search if it has not started yet
+    library_search_cancel: Arc<AtomicBool>,
 }
 
 impl App {
     pub fn new(store: Arc<model::Store>) -> Self {
-        let player = Arc::new(HlsPlayer::new(Arc::new(downloader::Downloader::new(
-            store.clone(),
-        ))));
+        let downloader = Arc::new(downloader::Downloader::new(store.clone()));
+        let player = Arc::new(HlsPlayer::new(downloader.clone()));
 
         let navigation = vec![Page::default()].into();
 
@@ -52,8 +71,11 @@ impl App {
             navigation,
             store,
             player,
+            downloader,
             controls: ControlsElement::new(),
             cur_page_index: 0,
+            library_query: Default::default(),
+            library_search_cancel: Default::default(),
         };
 
         zelf
@@ -86,27 +108,66 @@ pub enum Message {
 
     QueueChanged(VecDeque<audio::SongId>),
     QueueResolved(Vec<Arc<model::Song>>),
-    SongListFilterComputed(HashMap<model::Id, Display>),
+    /// Filter generation and the result
+    SongListFilterComputed(u64, Vec<Display>),
     PlaylistResolved(Arc<model::Playlist>),
     CurSongChange(Option<audio::SongId>),
-    CurSongResolved(Option<Arc<model::Song>>),
+    CurSongResolved(Option<(Arc<model::Song>, model::Eventually<widget::image::Handle>)>),
+    WaveformResolved(model::Id, Option<Arc<model::Waveform>>),
+    CurSongCommentsResolved(model::Id, Option<Arc<Vec<model::Comment>>>),
 
     // UI
     UserClicked(Arc<model::User>),
+    UserResolved(Arc<model::User>),
+    UserTabChanged(UserTab),
+    UserSongsResolved(model::Id, UserTab, Result<Arc<model::Playlist>, String>),
+    UserListResolved(
+        model::UserListKey,
+        Result<Arc<Vec<Arc<model::User>>>, String>,
+    ),
     PlaylistClicked(Arc<model::Playlist>),
+    /// Load the playlist and then open it
+    OpenPlaylist(model::Id),
+    SongClicked(Arc<model::Song>),
+    TrackPlaylistsResolved(model::Id, Result<Arc<Vec<model::PlaylistSummary>>, String>),
+    TrackTabChanged(TrackTab),
+    TrackCommentsResolved(model::Id, Result<Arc<Vec<model::Comment>>, String>),
     SongQueue(Arc<model::Song>),
     SongPlay(Arc<model::Song>),
+    /// Play this song followed by songs like it
+    StartStation(Arc<model::Song>),
+    /// Replace the queue with the current song list as shown, starting from the song at
+    /// this index in playlist order
+    PlayFromHere(usize),
     PlaylistFilterChange(String),
+    LibraryQueryChange(String),
+    LibrarySearch,
+    LibraryResults(Arc<library::LibraryResults>),
+    /// Search all of SoundCloud rather than just what has been loaded
+    SoundCloudSearch(String),
+    DiscoverClicked,
+    DiscoverSelect(model::CollectionKey),
+    DiscoverResolved(model::CollectionKey, Result<Arc<model::Playlist>, String>),
+    DiscoverTagChange(String),
+    DiscoverTagSubmit,
+    /// Fetch what is on the discover page again, charts change throughout the day
+    DiscoverRefresh,
+    LogCacheMetrics,
+    SongListSort(SortColumn),
     PageChange(isize),
-    PageScroll(f32),
+    PageScroll(widget::scrollable::Viewport),
     VolumeChange(f32),
     NavigateForward,
     NavigateBack,
     QueuePlaylist,
     LoopingChanged,
+    SkipPreviewsChanged,
+    AutoplayChanged,
     Resume,
     Pause,
     Skip,
+    /// Seek to this many seconds into the current song
+    Seek(f32),
 }
 
 impl Message {
@@ -123,7 +184,10 @@ impl Application for App {
     type Flags = ();
 
     fn new(_flags: Self::Flags) -> (Self, Command<Self::Message>) {
-        let store = Arc::new(model::Store::new());
+        let client = http::HttpConfig::default()
+            .build()
+            .expect("Failed to create http client");
+        let store = Arc::new(model::Store::new(client));
 
         (
             Self::new(store.clone()),
@@ -163,7 +227,8 @@ impl Application for App {
             Message::None(_) | Message::Tick | Message::PlayerState(_) => Command::none(),
             Message::PlaylistResolved(playlist) => self.playlist_loaded(playlist),
             Message::SongQueue(song) => self.queue_song(&song),
-            Message::SongPlay(song) => self.queue_song(&song),
+            Message::SongPlay(song) => self.play_song(&song),
+            Message::StartStation(song) => self.start_station(&song),
             Message::Resume => {
                 let player = self.player.clone();
                 Command::perform(
@@ -182,6 +247,15 @@ impl Application for App {
                     Message::None,
                 )
             }
+            Message::Seek(secs) => {
+                let player = self.player.clone();
+                Command::perform(
+                    async move {
+                        player.seek(secs).await.unwrap();
+                    },
+                    Message::None,
+                )
+            }
             Message::Skip => {
                 let player = self.player.clone();
                 Command::perform(
@@ -215,38 +289,280 @@ impl Application for App {
             }
             Message::QueueResolved(queue) => self.controls.queue_changed(&queue),
             Message::QueuePlaylist => self.queue_playlist(),
+            Message::PlayFromHere(index) => self.play_from_here(index),
             Message::PlaylistFilterChange(string) => self.playlist_filter_changed(&string),
+            Message::LibraryQueryChange(query) => {
+                self.library_query = query.clone();
+                // NOTE(emily): Keep an open search page up to date whilst typing
+                match self.page_mut() {
+                    Page::Search(page) => {
+                        page.query = query;
+                        self.search_library(true)
+                    }
+                    _ => Command::none(),
+                }
+            }
+            Message::LibrarySearch => {
+                let query = self.library_query.clone();
+                match self.page_mut() {
+                    Page::Search(page) => page.query = query,
+                    _ => self.push_page(Page::Search(SearchPage::new(query))),
+                }
+                self.search_library(false)
+            }
+            Message::LibraryResults(results) => {
+                if let Page::Search(page) = self.page_mut() {
+                    page.results_ready(results);
+                }
+                Command::none()
+            }
+            Message::SoundCloudSearch(query) => {
+                let key = model::CollectionKey::Search(query.clone());
+                let mut page = DiscoverPage::new();
+                page.select(key.clone());
+                self.push_page(Page::Discover(page));
+
+                let store = self.store.clone();
+                Command::perform(
+                    async move {
+                        let songs = store.search(&query).await.map_err(|err| err.to_string());
+                        (key, songs)
+                    },
+                    |(key, songs)| Message::DiscoverResolved(key, songs),
+                )
+            }
             Message::UserClicked(user) => {
                 info!("User clicked");
 
                 self.push_page(Page::User(UserPage::new(user.clone(), &self.store)));
 
                 let store = self.store.clone();
+                let id = user.id;
+                let details =
+                    Command::perform(
+                        async move { store.user(&id).await },
+                        move |user| match user {
+                            Ok(user) => Message::UserResolved(user),
+                            Err(err) => {
+                                warn!("Failed to look up user {id}: {err}");
+                                Message::none()
+                            }
+                        },
+                    );
 
                 // TODO(emily): These Pages should eb components and then they cn make these requests on their own
                 // without us having to do this GARBAGE here.
-                Command::perform(
-                    async move { store.likes(&user.id).await.unwrap() },
-                    Message::PlaylistResolved,
-                )
+                let likes = self.load_user_songs(user.id, UserTab::Likes);
+
+                Command::batch([details, likes])
+            }
+            Message::UserResolved(user) => {
+                if let Page::User(page) = self.page_mut() {
+                    page.user_resolved(user);
+                }
+                Command::none()
+            }
+            Message::UserTabChanged(tab) => {
+                let Page::User(page) = self.page_mut() else {
+                    return Command::none();
+                };
+
+                match page.select_tab(tab) {
+                    Some(TabLoad::Songs(id, tab)) => self.load_user_songs(id, tab),
+                    Some(TabLoad::Users(key)) => {
+                        let store = self.store.clone();
+                        Command::perform(
+                            async move { store.user_list(&key).await.map_err(|err| err.to_string()) },
+                            move |users| Message::UserListResolved(key, users),
+                        )
+                    }
+                    None => Command::none(),
+                }
+            }
+            Message::UserSongsResolved(id, tab, songs) => {
+                if let Page::User(page) = self.page_mut() {
+                    page.songs_resolved(id, tab, songs);
+                }
+                Command::none()
+            }
+            Message::UserListResolved(key, users) => {
+                if let Page::User(page) = self.page_mut() {
+                    page.user_list_resolved(key, users);
+                }
+                Command::none()
             }
             Message::PlaylistClicked(playlist) => {
                 self.push_page(Page::Playlist(PlaylistPage::new(playlist)));
                 Command::none()
             }
-            Message::SongListFilterComputed(computed) => self.song_list_filter_computed(&computed),
+            Message::OpenPlaylist(id) => {
+                let store = self.store.clone();
+                Command::perform(async move { store.playlist(&id).await }, move |playlist| {
+                    match playlist {
+                        Ok(playlist) => Message::PlaylistClicked(playlist),
+                        Err(err) => {
+                            warn!("Failed to open playlist {id}: {err}");
+                            Message::none()
+                        }
+                    }
+                })
+            }
+            Message::SongClicked(song) => {
+                self.push_page(Page::Track(TrackPage::new(song.clone(), &self.store)));
+
+                let store = self.store.clone();
+                let id = song.id;
+                Command::perform(
+                    async move {
+                        store
+                            .song_playlists(&id)
+                            .await
+                            .map_err(|err| err.to_string())
+                    },
+                    move |playlists| Message::TrackPlaylistsResolved(id, playlists),
+                )
+            }
+            Message::TrackPlaylistsResolved(id, playlists) => {
+                if let Page::Track(page) = self.page_mut() {
+                    page.playlists_resolved(id, playlists);
+                }
+                Command::none()
+            }
+            Message::TrackTabChanged(tab) => {
+                let Page::Track(page) = self.page_mut() else {
+                    return Command::none();
+                };
+
+                // NOTE(emily): Comments are only loaded once somebody wants to see them
+                if !page.select_tab(tab) {
+                    return Command::none();
+                }
+
+                let store = self.store.clone();
+                let id = page.song.id;
+                Command::perform(
+                    async move {
+                        store
+                            .song_comments(&id)
+                            .await
+                            .map_err(|err| err.to_string())
+                    },
+                    move |comments| Message::TrackCommentsResolved(id, comments),
+                )
+            }
+            Message::TrackCommentsResolved(id, comments) => {
+                if let Page::Track(page) = self.page_mut() {
+                    page.comments_resolved(id, comments);
+                }
+                Command::none()
+            }
+            Message::DiscoverClicked => {
+                let page = DiscoverPage::new();
+                let key = page.selected.clone();
+                self.push_page(Page::Discover(page));
+                self.load_discover(key, false)
+            }
+            Message::DiscoverSelect(key) => {
+                let Page::Discover(page) = self.page_mut() else {
+                    return Command::none();
+                };
+                page.select(key.clone());
+                self.load_discover(key, false)
+            }
+            Message::DiscoverRefresh => {
+                let Page::Discover(page) = self.page_mut() else {
+                    return Command::none();
+                };
+                let key = page.selected.clone();
+                page.select(key.clone());
+                self.load_discover(key, true)
+            }
+            Message::LogCacheMetrics => {
+                let store = self.store.clone();
+                Command::perform(
+                    async move { store.log_cache_metrics().await },
+                    Message::None,
+                )
+            }
+            Message::DiscoverResolved(key, songs) => {
+                if let Page::Discover(page) = self.page_mut() {
+                    page.resolved(key, songs);
+                }
+                Command::none()
+            }
+            Message::DiscoverTagChange(text) => {
+                if let Page::Discover(page) = self.page_mut() {
+                    page.tag_text = text;
+                }
+                Command::none()
+            }
+            Message::DiscoverTagSubmit => {
+                let Page::Discover(page) = self.page_mut() else {
+                    return Command::none();
+                };
+                let Some(key) = page.tag_key() else {
+                    return Command::none();
+                };
+                page.select(key.clone());
+                self.load_discover(key, false)
+            }
+            Message::SongListFilterComputed(generation, computed) => {
+                self.song_list_filter_computed(generation, computed)
+            }
+            Message::SongListSort(column) => match self.song_list_mut() {
+                Some(song_list) => song_list.sort_by(column),
+                None => Command::none(),
+            },
             Message::CurSongChange(Some(id)) => {
                 let store = self.store.clone();
                 Command::perform(
-                    async move { store.song(&id).await.ok() },
+                    async move {
+                        let song = store.song(&id).await.ok()?;
+                        let artwork = store
+                            .lazy_image(song.artwork_url.as_deref(), model::ImageSize::Controls);
+                        Some((song, artwork))
+                    },
                     Message::CurSongResolved,
                 )
             }
             Message::CurSongChange(None) => {
                 Command::perform(async { None }, Message::CurSongResolved)
             }
-            Message::CurSongResolved(song) => {
-                self.controls.set_cur_song(song);
+            Message::CurSongResolved(cur_song) => match cur_song {
+                Some((song, artwork)) => {
+                    let id = song.id;
+                    self.controls.set_cur_song(Some(song.clone()), artwork);
+
+                    let store = self.store.clone();
+                    let waveform = Command::perform(
+                        async move {
+                            // NOTE(emily): Not every song has a waveform, the controls just
+                            // draw flat bars for those
+                            let waveform = store.waveform(&song).await.ok();
+                            (song.id, waveform)
+                        },
+                        |(id, waveform)| Message::WaveformResolved(id, waveform),
+                    );
+
+                    let store = self.store.clone();
+                    let comments = Command::perform(
+                        async move { store.song_comments(&id).await.ok() },
+                        move |comments| Message::CurSongCommentsResolved(id, comments),
+                    );
+
+                    Command::batch([waveform, comments])
+                }
+                None => {
+                    self.controls.set_cur_song(None, model::Eventually::never());
+                    Command::none()
+                }
+            },
+            Message::WaveformResolved(id, waveform) => {
+                self.controls.waveform_resolved(id, waveform);
+                Command::none()
+            }
+            Message::CurSongCommentsResolved(id, comments) => {
+                self.controls.comments_resolved(id, comments);
                 Command::none()
             }
 
@@ -255,15 +571,19 @@ impl Application for App {
                     Page::Main => todo!(),
                     Page::Playlist(playlist_page) => playlist_page.page_changed(amount),
                     Page::User(_) => todo!(),
+                    Page::Discover(discover_page) => discover_page.page_changed(amount),
+                    Page::Search(_) | Page::Track(_) => {}
                 };
 
                 Command::none()
             }
-            Message::PageScroll(amount) => {
+            Message::PageScroll(viewport) => {
                 match self.page_mut() {
                     Page::Main => todo!(),
-                    Page::Playlist(playlist) => playlist.page_scroll(amount),
-                    Page::User(user) => user.page_scroll(amount),
+                    Page::Playlist(playlist) => playlist.page_scroll(viewport),
+                    Page::User(user) => user.page_scroll(viewport),
+                    Page::Discover(discover) => discover.page_scroll(viewport),
+                    Page::Search(_) | Page::Track(_) => {}
                 };
                 Command::none()
             }
@@ -286,6 +606,31 @@ impl Application for App {
                 }
                 Command::none()
             }
+            Message::SkipPreviewsChanged => {
+                let skip_previews = self.controls.toggle_skip_previews();
+                self.downloader
+                    .skip_previews
+                    .store(skip_previews, Ordering::Relaxed);
+                Command::none()
+            }
+            Message::AutoplayChanged => {
+                let autoplay = self.controls.toggle_autoplay();
+                // NOTE(emily): Looping means the queue never runs out, so autoplay would
+                // never get a chance to do anything
+                if autoplay {
+                    self.controls.set_looping(audio::Looping::None);
+                }
+                let player = self.player.clone();
+                Command::perform(
+                    async move {
+                        if autoplay {
+                            player.looping(audio::Looping::None).await.unwrap();
+                        }
+                        player.autoplay(autoplay).await.unwrap();
+                    },
+                    Message::None,
+                )
+            }
             Message::LoopingChanged => {
                 // Get looping from controls
                 let looping = self.controls.rotate_looping();
@@ -307,6 +652,9 @@ impl Application for App {
             watch_subscription("player song", self.player.cur_song()).map(Message::CurSongChange),
             watch_subscription("queue changed", self.player.queued_watch())
                 .map(Message::QueueChanged),
+            // NOTE(emily): Nothing to do here but redraw, which happens after any message
+            watch_subscription("images loaded", self.store.images_loaded()).map(|_| Message::Tick),
+            iced::time::every(CACHE_METRICS_INTERVAL).map(|_| Message::LogCacheMetrics),
         ])
     }
 
@@ -314,12 +662,22 @@ impl Application for App {
         widget::container(widget::column!(
             widget::row!(
                 widget::button(widget::text("<")).on_press(Message::NavigateBack),
-                widget::button(widget::text(">")).on_press(Message::NavigateForward)
-            ),
+                widget::button(widget::text(">")).on_press(Message::NavigateForward),
+                widget::button(widget::text("Discover")).on_press(Message::DiscoverClicked),
+                widget::Space::with_width(iced::Length::Fill),
+                widget::text_input("Search library...", &self.library_query)
+                    .on_input(Message::LibraryQueryChange)
+                    .on_submit(Message::LibrarySearch)
+                    .width(iced::Length::Fixed(300.0)),
+            )
+            .spacing(10),
             widget::container(match self.page() {
                 Page::Main => widget::text("Main page").into(),
                 Page::Playlist(playlist_page) => playlist_page.view(),
                 Page::User(user_page) => user_page.view(),
+                Page::Search(search_page) => search_page.view(),
+                Page::Track(track_page) => track_page.view(),
+                Page::Discover(discover_page) => discover_page.view(),
             })
             .height(iced::Length::FillPortion(1)),
             widget::container(widget::column!(
@@ -347,7 +705,7 @@ impl App {
         match self.page_mut() {
             Page::Main => todo!(),
             Page::Playlist(_) => todo!(),
-            Page::User(page) => page.update_songs(playlist.clone()),
+            Page::User(_) | Page::Search(_) | Page::Track(_) | Page::Discover(_) => {}
         };
 
         Command::none()
@@ -355,16 +713,79 @@ impl App {
 
     fn song_list_filter_computed(
         &mut self,
-        computed: &HashMap<model::Id, Display>,
+        generation: u64,
+        computed: Vec<Display>,
     ) -> Command<Message> {
         info!("Filter computed");
-        match self.page_mut() {
-            Page::Playlist(page) => page.song_list.filter_computed(computed),
-            Page::Main => todo!(),
-            Page::User(_) => todo!(),
+        // NOTE(emily): The page might have changed since the filter was started,
+        // in which case the generation wont match and the result is dropped
+        match self.song_list_mut() {
+            Some(song_list) => song_list.filter_computed(generation, computed),
+            None => Command::none(),
         }
     }
 
+    fn load_user_songs(&self, id: model::Id, tab: UserTab) -> Command<Message> {
+        let store = self.store.clone();
+        Command::perform(
+            async move {
+                let songs = match tab {
+                    UserTab::Tracks => store.songs(&id).await,
+                    UserTab::Reposts => store.reposts(&id).await,
+                    _ => store.likes(&id).await,
+                };
+                songs.map_err(|err| err.to_string())
+            },
+            move |songs| Message::UserSongsResolved(id, tab, songs),
+        )
+    }
+
+    fn load_discover(&self, key: model::CollectionKey, refresh: bool) -> Command<Message> {
+        let store = self.store.clone();
+        Command::perform(
+            async move {
+                let songs = if refresh {
+                    store.refresh_collection(&key).await
+                } else {
+                    store.collection(&key).await
+                };
+                let songs = songs.map_err(|err| err.to_string());
+                (key, songs)
+            },
+            |(key, songs)| Message::DiscoverResolved(key, songs),
+        )
+    }
+
+    /// Search the library for library_query. Whilst typing the search waits for the query
+    /// to settle, and any search that is still waiting is cancelled either way.
+    fn search_library(&mut self, debounce: bool) -> Command<Message> {
+        self.library_search_cancel.store(true, Ordering::Relaxed);
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.library_search_cancel = cancel.clone();
+
+        let store = self.store.clone();
+        let query = self.library_query.clone();
+        Command::perform(
+            async move {
+                if debounce {
+                    tokio::time::sleep(LIBRARY_SEARCH_DEBOUNCE).await;
+                }
+                if cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
+                Some(store.search_library(&query).await)
+            },
+            |results| match results {
+                Some(Ok(results)) => Message::LibraryResults(Arc::new(results)),
+                Some(Err(err)) => {
+                    warn!("Library search failed: {err}");
+                    Message::none()
+                }
+                None => Message::none(),
+            },
+        )
+    }
+
     fn queue_song(&self, song: &Arc<model::Song>) -> iced::Command<Message> {
         let player = self.player.clone();
         let id = song.id;
@@ -376,6 +797,91 @@ impl App {
         )
     }
 
+    fn song_list(&self) -> Option<&SongList> {
+        match self.page() {
+            Page::Main => None,
+            Page::Playlist(page) => Some(&page.song_list),
+            Page::User(page) => page.song_list(),
+            Page::Discover(page) => page.song_list.as_ref(),
+            Page::Search(_) | Page::Track(_) => None,
+        }
+    }
+
+    fn song_list_mut(&mut self) -> Option<&mut SongList> {
+        match self.page_mut() {
+            Page::Main => None,
+            Page::Playlist(page) => Some(&mut page.song_list),
+            Page::User(page) => page.song_list_mut(),
+            Page::Discover(page) => page.song_list.as_mut(),
+            Page::Search(_) | Page::Track(_) => None,
+        }
+    }
+
+    fn play_from_here(&self, index: usize) -> iced::Command<Message> {
+        let Some(song_list) = self.song_list() else {
+            return Command::none();
+        };
+
+        let player = self.player.clone();
+        // NOTE(emily): Queue what is on screen, so sorting and filtering carry over
+        let ids = song_list
+            .displayed_from(index)
+            .map(|s| s.model())
+            .filter(|s| s.availability.is_playable())
+            .map(|s| s.id)
+            .collect();
+        Command::perform(
+            async move {
+                player.replace_queue(ids).await.unwrap();
+            },
+            Message::None,
+        )
+    }
+
+    fn play_song(&self, song: &Arc<model::Song>) -> iced::Command<Message> {
+        let player = self.player.clone();
+        let id = song.id;
+        Command::perform(
+            async move {
+                player.play_now(id).await.unwrap();
+            },
+            Message::None,
+        )
+    }
+
+    fn start_station(&mut self, song: &Arc<model::Song>) -> iced::Command<Message> {
+        // NOTE(emily): A station keeps going once the related songs run out, which
+        // only happens if we arent looping
+        self.controls.set_autoplay(true);
+        self.controls.set_looping(audio::Looping::None);
+
+        let player = self.player.clone();
+        let store = self.store.clone();
+        let id = song.id;
+        Command::perform(
+            async move {
+                let related = store.related(&id).await.unwrap_or_else(|err| {
+                    warn!("Failed to get songs related to {id}: {err}");
+                    Default::default()
+                });
+
+                let ids = std::iter::once(id)
+                    .chain(
+                        related
+                            .iter()
+                            .filter(|song| song.id != id && song.availability.is_playable())
+                            .map(|song| song.id),
+                    )
+                    .collect();
+
+                player.looping(audio::Looping::None).await.unwrap();
+                player.autoplay(true).await.unwrap();
+                player.replace_queue(ids).await.unwrap();
+            },
+            Message::None,
+        )
+    }
+
     fn playlist_filter_changed(&mut self, string: &str) -> iced::Command<Message> {
         if let Page::Playlist(page) = self.page_mut() {
             page.filter_changed(string)
@@ -387,7 +893,11 @@ impl App {
     fn queue_playlist(&mut self) -> iced::Command<Message> {
         if let Page::Playlist(page) = self.page() {
             let player = self.player.clone();
-            let ids = page.songs().map(|s| s.id).collect();
+            let ids = page
+                .songs()
+                .filter(|s| s.availability.is_playable())
+                .map(|s| s.id)
+                .collect();
             iced::Command::perform(
                 async move {
                     player.queue_many(ids).await.unwrap();
@@ -1,9 +1,14 @@
 use super::app::Message;
+use super::waveform::Waveform;
 use crate::model::{self};
 use ellipse::Ellipse;
 use iced::{widget, Command, Element, Length};
 use std::{ops::RangeInclusive, sync::Arc};
 
+/// How long a comment is shown for after playback reaches it
+const COMMENT_SHOW_MS: usize = 4000;
+const COMMENT_COLOR: iced::Color = iced::Color::from_rgb(0.6, 0.6, 0.6);
+
 fn format_duration(duration: &std::time::Duration) -> String {
     let secs = duration.as_secs();
     let hours = secs / 3600;
@@ -20,19 +25,32 @@ fn format_duration(duration: &std::time::Duration) -> String {
 pub struct ControlsElement {
     options: Vec<String>,
     cur_song: Option<Arc<model::Song>>,
+    /// Artwork for cur_song at the size that we show it at
+    artwork: model::Eventually<widget::image::Handle>,
+    /// Waveform for cur_song, None until it is loaded (or if it doesnt have one)
+    waveform: Option<Arc<model::Waveform>>,
+    /// Comments on cur_song, sorted by timestamp
+    comments: Option<Arc<Vec<model::Comment>>>,
     player_state: audio::PlayerState,
     volume: f32,
     looping: audio::Looping,
+    skip_previews: bool,
+    autoplay: bool,
 }
 
 impl ControlsElement {
     pub fn new() -> Self {
         Self {
             cur_song: None,
+            artwork: model::Eventually::never(),
+            waveform: None,
+            comments: None,
             options: vec![],
             player_state: Default::default(),
             volume: 100.0,
             looping: audio::Looping::LoopOne,
+            skip_previews: false,
+            autoplay: false,
         }
     }
 
@@ -51,8 +69,39 @@ impl ControlsElement {
         Command::none()
     }
 
-    pub fn set_cur_song(&mut self, song: Option<Arc<model::Song>>) {
+    pub fn set_cur_song(
+        &mut self,
+        song: Option<Arc<model::Song>>,
+        artwork: model::Eventually<widget::image::Handle>,
+    ) {
+        artwork.request();
         self.cur_song = song;
+        self.artwork = artwork;
+        self.waveform = None;
+        self.comments = None;
+    }
+
+    pub fn comments_resolved(&mut self, id: model::Id, comments: Option<Arc<Vec<model::Comment>>>) {
+        if self.cur_song.as_ref().map(|s| s.id) == Some(id) {
+            self.comments = comments;
+        }
+    }
+
+    /// The most recent comment that playback has passed, if it was recent enough
+    fn current_comment(&self, position_ms: usize) -> Option<&model::Comment> {
+        let comments = self.comments.as_deref()?;
+        // NOTE(emily): This relies on comments being sorted by timestamp with the ones that
+        // dont have one last, which Store::song_comments makes sure of
+        let passed = comments.partition_point(|c| c.timestamp.map_or(false, |t| t <= position_ms));
+        let comment = comments.get(passed.checked_sub(1)?)?;
+
+        (position_ms - comment.timestamp? < COMMENT_SHOW_MS).then_some(comment)
+    }
+
+    pub fn waveform_resolved(&mut self, id: model::Id, waveform: Option<Arc<model::Waveform>>) {
+        if self.cur_song.as_ref().map(|s| s.id) == Some(id) {
+            self.waveform = waveform;
+        }
     }
 
     pub fn view(&self) -> Element<Message> {
@@ -69,12 +118,7 @@ impl ControlsElement {
             audio::Playing::Paused => widget::button(widget::text(">")).on_press(Message::Resume),
         };
 
-        let artwork = if let Some(artwork) = self
-            .cur_song
-            .as_ref()
-            .and_then(|s| s.artwork.clone())
-            .as_ref()
-        {
+        let artwork = if let Some(artwork) = self.artwork.maybe() {
             widget::container(widget::Image::new(artwork.as_ref().clone()))
         } else {
             widget::container(widget::row!())
@@ -82,11 +126,20 @@ impl ControlsElement {
         .height(Length::Fixed(75.0))
         .width(Length::Fixed(75.0));
 
+        let comment = self
+            .current_comment(location.as_millis() as usize)
+            .map(|c| format!("{}: {}", c.username, c.body))
+            .unwrap_or_default();
+
         let song_title_user = widget::container(widget::column!(
             widget::text(
                 self.cur_song
                     .as_ref()
-                    .map(|s| s.title.clone())
+                    .map(|s| if s.availability == model::Availability::Snippet {
+                        format!("{} (preview)", s.title)
+                    } else {
+                        s.title.clone()
+                    })
                     .unwrap_or_default()
             )
             .size(16),
@@ -96,7 +149,10 @@ impl ControlsElement {
                     .map(|s| s.user.username.clone())
                     .unwrap_or_default()
             )
-            .size(13)
+            .size(13),
+            widget::text(comment.as_str().truncate_ellipse(40))
+                .size(12)
+                .style(COMMENT_COLOR)
         ));
 
         let controls = widget::container(
@@ -113,6 +169,18 @@ impl ControlsElement {
                         audio::Looping::None => "no loop",
                     }))
                     .on_press(Message::LoopingChanged),
+                    widget::button(widget::text(if self.skip_previews {
+                        "skip previews"
+                    } else {
+                        "play previews"
+                    }))
+                    .on_press(Message::SkipPreviewsChanged),
+                    widget::button(widget::text(if self.autoplay {
+                        "autoplay"
+                    } else {
+                        "no autoplay"
+                    }))
+                    .on_press(Message::AutoplayChanged),
                     widget::row!().width(Length::FillPortion(1)),
                 )
                 .align_items(iced::Alignment::Center)
@@ -120,11 +188,14 @@ impl ControlsElement {
                 .width(Length::Fill),
                 widget::row!(
                     widget::text(format!("{}", format_duration(&location))),
-                    widget::slider(
-                        RangeInclusive::new(0.0, total.as_secs_f64()),
-                        location.as_secs_f64(),
-                        |_| Message::None(()),
-                    ),
+                    Waveform::new(
+                        self.waveform.as_deref(),
+                        location.as_secs_f32(),
+                        self.player_state.buffered,
+                        total.as_secs_f32(),
+                    )
+                    .comments(self.comments.as_deref().map_or(&[], |c| c.as_slice()))
+                    .view(30.0),
                     iced::widget::text(format!("{}", format_duration(&total))),
                 )
                 .align_items(iced::Alignment::Center)
@@ -167,6 +238,24 @@ impl ControlsElement {
         self.volume = volume * 100.0;
     }
 
+    pub(crate) fn toggle_skip_previews(&mut self) -> bool {
+        self.skip_previews = !self.skip_previews;
+        self.skip_previews
+    }
+
+    pub(crate) fn toggle_autoplay(&mut self) -> bool {
+        self.autoplay = !self.autoplay;
+        self.autoplay
+    }
+
+    pub(crate) fn set_autoplay(&mut self, autoplay: bool) {
+        self.autoplay = autoplay;
+    }
+
+    pub(crate) fn set_looping(&mut self, looping: audio::Looping) {
+        self.looping = looping;
+    }
+
     pub(crate) fn rotate_looping(&mut self) -> audio::Looping {
         self.looping = match self.looping {
             audio::Looping::None => audio::Looping::LoopOne,
@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+use iced::widget;
+use iced::Element;
+use iced::Length;
+
+use crate::model::{self, ChartKind, CollectionKey};
+
+use super::app::Message;
+use super::song_list::SongList;
+
+const GENRES_WIDTH: f32 = 200.0;
+
+/// Genres that SoundCloud has charts for, as (name, genre in the chart urn)
+const GENRES: &[(&str, &str)] = &[
+    ("All music", "all-music"),
+    ("Alternative Rock", "alternativerock"),
+    ("Ambient", "ambient"),
+    ("Classical", "classical"),
+    ("Country", "country"),
+    ("Dance & EDM", "danceedm"),
+    ("Dancehall", "dancehall"),
+    ("Deep House", "deephouse"),
+    ("Disco", "disco"),
+    ("Drum & Bass", "drumbass"),
+    ("Dubstep", "dubstep"),
+    ("Electronic", "electronic"),
+    ("Folk & Singer-Songwriter", "folksingersongwriter"),
+    ("Hip-hop & Rap", "hiphoprap"),
+    ("House", "house"),
+    ("Indie", "indie"),
+    ("Jazz & Blues", "jazzblues"),
+    ("Latin", "latin"),
+    ("Metal", "metal"),
+    ("Piano", "piano"),
+    ("Pop", "pop"),
+    ("R&B & Soul", "rbsoul"),
+    ("Reggae", "reggae"),
+    ("Reggaeton", "reggaeton"),
+    ("Rock", "rock"),
+    ("Soundtrack", "soundtrack"),
+    ("Techno", "techno"),
+    ("Trance", "trance"),
+    ("Trap", "trap"),
+    ("Triphop", "triphop"),
+    ("World", "world"),
+];
+
+/// Charts by genre and songs by tag, from all of SoundCloud rather than someones library
+pub struct DiscoverPage {
+    /// What is being shown (or loaded)
+    pub selected: CollectionKey,
+    /// Chart kind to use when a genre is picked, so that switching genre keeps it
+    kind: ChartKind,
+    genre: String,
+    pub tag_text: String,
+    /// None while selected is loading
+    pub song_list: Option<SongList>,
+    error: Option<String>,
+}
+
+impl DiscoverPage {
+    pub fn new() -> Self {
+        let (kind, genre) = (ChartKind::Top, GENRES[0].1.to_string());
+        Self {
+            selected: CollectionKey::Chart(kind, genre.clone()),
+            kind,
+            genre,
+            tag_text: Default::default(),
+            song_list: None,
+            error: None,
+        }
+    }
+
+    /// Start showing key, the songs for it need loading
+    pub fn select(&mut self, key: CollectionKey) {
+        if let CollectionKey::Chart(kind, genre) = &key {
+            self.kind = *kind;
+            self.genre = genre.clone();
+        }
+
+        self.selected = key;
+        self.song_list = None;
+        self.error = None;
+    }
+
+    pub fn resolved(&mut self, key: CollectionKey, songs: Result<Arc<model::Playlist>, String>) {
+        // NOTE(emily): Something else might have been picked while this was loading
+        if key != self.selected {
+            return;
+        }
+
+        match songs {
+            Ok(songs) => self.song_list = Some(SongList::new(songs)),
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    /// Key for the tag that has been typed in, if there is one
+    pub fn tag_key(&self) -> Option<CollectionKey> {
+        let tag = self.tag_text.trim().trim_start_matches('#');
+        (!tag.is_empty()).then(|| CollectionKey::Tag(tag.to_lowercase()))
+    }
+
+    fn option<'a>(label: &str, key: CollectionKey, selected: bool) -> Element<'a, Message> {
+        let button = widget::button(widget::text(label)).width(Length::Fill);
+        if selected {
+            button.into()
+        } else {
+            button
+                .style(iced::theme::Button::Text)
+                .on_press(Message::DiscoverSelect(key))
+                .into()
+        }
+    }
+
+    fn charts(&self) -> Element<Message> {
+        let showing_chart = matches!(self.selected, CollectionKey::Chart(..));
+
+        let kinds = [ChartKind::Top, ChartKind::Trending].into_iter().fold(
+            widget::row!().spacing(10),
+            |row, kind| {
+                row.push(
+                    widget::container(Self::option(
+                        kind.title(),
+                        CollectionKey::Chart(kind, self.genre.clone()),
+                        showing_chart && kind == self.kind,
+                    ))
+                    .width(Length::Shrink),
+                )
+            },
+        );
+
+        let genres = GENRES
+            .iter()
+            .fold(widget::column!().spacing(5), |column, (name, genre)| {
+                column.push(Self::option(
+                    name,
+                    CollectionKey::Chart(self.kind, genre.to_string()),
+                    showing_chart && *genre == self.genre,
+                ))
+            });
+
+        widget::column!(
+            kinds,
+            widget::text_input("Browse a tag... (e.g. lofi)", &self.tag_text)
+                .on_input(Message::DiscoverTagChange)
+                .on_submit(Message::DiscoverTagSubmit),
+            widget::scrollable(genres),
+        )
+        .spacing(20)
+        .width(Length::Fixed(GENRES_WIDTH))
+        .into()
+    }
+
+    fn songs(&self) -> Element<Message> {
+        match (&self.song_list, &self.error) {
+            (_, Some(err)) => widget::text(format!("Could not load songs: {err}")).into(),
+            (Some(song_list), None) => widget::column!(
+                widget::row!(
+                    widget::text(song_list.title()).size(24),
+                    widget::button(widget::text("Refresh"))
+                        .style(iced::theme::Button::Text)
+                        .on_press(Message::DiscoverRefresh),
+                )
+                .spacing(10)
+                .align_items(iced::Alignment::Center),
+                song_list.view()
+            )
+            .spacing(20)
+            .into(),
+            (None, None) => widget::text("Loading...").into(),
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        widget::column!(
+            widget::text("Discover").size(40),
+            widget::row!(self.charts(), self.songs()).spacing(20),
+        )
+        .spacing(20)
+        .into()
+    }
+
+    pub(crate) fn page_changed(&mut self, amount: isize) {
+        if let Some(song_list) = &mut self.song_list {
+            song_list.page_changed(amount);
+        }
+    }
+
+    pub(crate) fn page_scroll(&mut self, viewport: widget::scrollable::Viewport) {
+        if let Some(song_list) = &mut self.song_list {
+            song_list.page_scroll(viewport);
+        }
+    }
+}
@@ -1,10 +1,16 @@
 mod app;
 mod controls;
+mod discover_page;
 mod main_page;
+mod query;
 mod queue;
+mod search_page;
 mod song;
 mod song_list;
+mod track_page;
+mod user_grid;
 mod user_page;
+mod waveform;
 pub use app::App;
 
 mod playlist_page;
@@ -7,12 +7,19 @@ use iced::Element;
 use crate::model;
 
 use super::app::Message;
+use super::query::{ParseError, Query};
+use super::song::format_duration;
 use super::song_list::SongList;
 
+const ERROR_COLOR: iced::Color = iced::Color::from_rgb(0.9, 0.2, 0.2);
+const DIM_COLOR: iced::Color = iced::Color::from_rgb(0.6, 0.6, 0.6);
+
 pub struct PlaylistPage {
     pub playlist: Arc<model::Playlist>,
     pub song_list: SongList,
     pub filter_text: String,
+    /// Why filter_text could not be parsed. The last good filter stays applied.
+    pub filter_error: Option<ParseError>,
 }
 
 impl PlaylistPage {
@@ -21,38 +28,113 @@ impl PlaylistPage {
             playlist: playlist.clone(),
             song_list: SongList::new(playlist),
             filter_text: Default::default(),
+            filter_error: None,
         }
     }
 
     pub fn view(&self) -> Element<Message> {
         let mut column = widget::column!(
             widget::row!(
-                widget::text(format!(
-                    "{} ({} tracks)",
-                    self.song_list.title(),
-                    self.song_list.playlist().songs.len()
-                ))
-                .size(40)
+                widget::column!(
+                    widget::text(self.header()).size(40),
+                    widget::text(self.details()).style(DIM_COLOR),
+                )
+                .spacing(10)
                 .width(iced::Length::FillPortion(3)),
-                widget::text_input("Search...", &self.filter_text)
-                    .size(20)
-                    .on_input(Message::PlaylistFilterChange)
-                    .width(iced::Length::FillPortion(2)),
+                self.filter(),
             )
             .padding(10),
             widget::button(widget::text("Queue playlist")).on_press(Message::QueuePlaylist),
         )
         .spacing(40);
 
+        if let Some(description) = &self.playlist.description {
+            column = column.push(widget::text(description).size(14));
+        }
+
         column = column.push(self.song_list.view());
 
         column.into()
     }
 
+    fn filter(&self) -> Element<Message> {
+        let mut column = widget::column!(widget::text_input(
+            "Search... (e.g. artist:foo duration:>5m -remix)",
+            &self.filter_text
+        )
+        .size(20)
+        .on_input(Message::PlaylistFilterChange))
+        .spacing(5)
+        .width(iced::Length::FillPortion(2));
+
+        if let Some(err) = &self.filter_error {
+            column = column.push(widget::text(err.to_string()).size(14).style(ERROR_COLOR));
+        }
+
+        column.into()
+    }
+
+    fn header(&self) -> String {
+        let songs = &self.song_list.playlist().songs;
+        let unavailable = songs
+            .iter()
+            .filter(|s| !s.availability.is_playable())
+            .count();
+
+        if unavailable == 0 {
+            format!("{} ({} tracks)", self.song_list.title(), songs.len())
+        } else {
+            format!(
+                "{} ({} tracks, {} unavailable)",
+                self.song_list.title(),
+                songs.len(),
+                unavailable
+            )
+        }
+    }
+
+    /// e.g. Album, 12 tracks, 45:12, released 2020-01-01
+    fn details(&self) -> String {
+        let playlist = &self.playlist;
+
+        let track_count = playlist.track_count.unwrap_or(playlist.songs.len());
+        let duration = playlist
+            .duration
+            .unwrap_or_else(|| playlist.songs.iter().map(|s| s.full_duration).sum());
+
+        let mut details = vec![
+            playlist.kind(),
+            format!("{track_count} tracks"),
+            format_duration(duration),
+        ];
+
+        // NOTE(emily): Dates are shown without the time part
+        if let Some(released) = &playlist.release_date {
+            details.push(format!(
+                "Released {}",
+                released.get(..10).unwrap_or(released)
+            ));
+        }
+        if let Some(created) = &playlist.created_at {
+            details.push(format!("Created {}", created.get(..10).unwrap_or(created)));
+        }
+
+        details.join(" \u{b7} ")
+    }
+
     pub fn filter_changed(&mut self, str: &str) -> Command<Message> {
         self.filter_text = str.to_string();
 
-        self.song_list.update_filter(str)
+        match Query::parse(str) {
+            Ok(query) => {
+                self.filter_error = None;
+                self.song_list.update_filter(query)
+            }
+            Err(err) => {
+                self.filter_error = Some(err);
+                Command::none()
+            }
+        }
     }
 
     pub fn songs(&self) -> impl Iterator<Item = &'_ Arc<model::Song>> + '_ {
@@ -63,7 +145,7 @@ impl PlaylistPage {
         self.song_list.page_changed(amount);
     }
 
-    pub(crate) fn page_scroll(&mut self, amount: f32) {
-        self.song_list.page_scroll(amount);
+    pub(crate) fn page_scroll(&mut self, viewport: widget::scrollable::Viewport) {
+        self.song_list.page_scroll(viewport);
     }
 }
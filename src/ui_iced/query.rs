@@ -0,0 +1,560 @@
+use std::fmt;
+
+use super::song::{Highlights, Song};
+
+/// The filter query for song lists.
+///
+/// A query is a list of terms separated by whitespace, and a song has to match all of them:
+///
+/// - `foo` or `"foo bar"` fuzzy matches against the title and artist
+/// - `artist:foo` (or `user:foo`) and `title:"foo bar"` fuzzy match against just that
+/// - `genre:house` matches songs whose genre contains house
+/// - `duration:>10m` compares the length. Durations look like `90s`, `10m`, `1h30m` or `3:30`,
+///   a plain number is minutes.
+/// - `liked:<2024-01-01` (or `added:`) compares when the song was added to the list.
+///   Dates look like `2024`, `2024-01` or `2024-01-01`.
+/// - `plays:>=1000` and `likes:>50k` compare the counts
+/// - `-` in front of any of the above excludes songs that match it instead
+///
+/// Comparisons are one of `<`, `<=`, `=`, `>=` and `>`, and default to `=`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub negated: bool,
+    pub predicate: Predicate,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    /// Fuzzy match against title and artist
+    Text(String),
+    Artist(String),
+    Title(String),
+    Genre(String),
+    /// Milliseconds
+    Duration(Comparison, usize),
+    /// A date prefix (YYYY, YYYY-MM or YYYY-MM-DD) of when the song was added to the list
+    Added(Comparison, String),
+    Plays(Comparison, u64),
+    Likes(Comparison, u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessEq,
+    Eq,
+    GreaterEq,
+    Greater,
+}
+
+impl Comparison {
+    fn test<T: PartialOrd + ?Sized>(&self, value: &T, against: &T) -> bool {
+        match self {
+            Comparison::Less => value < against,
+            Comparison::LessEq => value <= against,
+            Comparison::Eq => value == against,
+            Comparison::GreaterEq => value >= against,
+            Comparison::Greater => value > against,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Character offset into the query of where things went wrong
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at character {})", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+const FIELDS: &str = "artist, title, genre, duration, liked, plays, likes";
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        Parser { input, pos: 0 }.parse()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Whether every song that matches self also matches previous, i.e. the query only got
+    /// more specific (more terms, or longer text in the same terms)
+    pub fn narrows(&self, previous: &Query) -> bool {
+        self.terms.len() >= previous.terms.len()
+            && previous
+                .terms
+                .iter()
+                .zip(&self.terms)
+                .all(|(previous, term)| term.narrows(previous))
+    }
+
+    /// None if song does not match, otherwise how well the free text parts matched
+    /// and which characters they matched
+    pub fn score(&self, song: &Song) -> Option<(i64, Highlights)> {
+        let mut score = 0;
+        let mut highlights = Highlights::default();
+
+        for term in &self.terms {
+            match (term.predicate.score(song), term.negated) {
+                (Some((term_score, term_highlights)), false) => {
+                    score += term_score;
+                    highlights.extend(term_highlights);
+                }
+                (None, true) => {}
+                _ => return None,
+            }
+        }
+
+        highlights.normalize();
+        Some((score, highlights))
+    }
+}
+
+impl Term {
+    fn narrows(&self, previous: &Term) -> bool {
+        if self == previous {
+            return true;
+        }
+
+        // NOTE(emily): A longer fuzzy pattern can only match a subset of what the shorter
+        // one did, and the same goes for contains. Excluding more does the opposite though.
+        if self.negated || previous.negated {
+            return false;
+        }
+
+        match (&self.predicate, &previous.predicate) {
+            (Predicate::Text(text), Predicate::Text(previous))
+            | (Predicate::Artist(text), Predicate::Artist(previous))
+            | (Predicate::Title(text), Predicate::Title(previous))
+            | (Predicate::Genre(text), Predicate::Genre(previous)) => text.starts_with(previous),
+            _ => false,
+        }
+    }
+}
+
+impl Predicate {
+    fn score(&self, song: &Song) -> Option<(i64, Highlights)> {
+        let model = song.model();
+
+        let matched = match self {
+            Predicate::Text(text) => return song.match_score(text),
+            Predicate::Artist(text) => return song.match_username(text),
+            Predicate::Title(text) => return song.match_title(text),
+            Predicate::Genre(genre) => model
+                .genre
+                .as_ref()
+                .map_or(false, |g| g.to_lowercase().contains(&genre.to_lowercase())),
+            Predicate::Duration(cmp, ms) => cmp.test(&model.full_duration, ms),
+            Predicate::Added(cmp, date) => {
+                let added = song.added_at()?;
+                // NOTE(emily): Compare at the precision that the date was given in,
+                // so that e.g. liked:>2023 means after 2023 and not after the first of January
+                let added = added.get(..date.len()).unwrap_or(added);
+                cmp.test(added, date.as_str())
+            }
+            Predicate::Plays(cmp, n) => cmp.test(&model.plays?, n),
+            Predicate::Likes(cmp, n) => cmp.test(&model.likes?, n),
+        };
+
+        // NOTE(emily): Structured predicates dont change the ranking, they only filter
+        matched.then(|| (0, Highlights::default()))
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    /// Byte offset into input
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, pos: usize, message: impl Into<String>) -> ParseError {
+        ParseError {
+            position: self.input[..pos].chars().count(),
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn at_end_of_term(&self) -> bool {
+        self.peek().map_or(true, char::is_whitespace)
+    }
+
+    /// Consume characters while f holds
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().map_or(false, &f) {
+            self.bump();
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn parse(mut self) -> Result<Query, ParseError> {
+        let mut terms = vec![];
+
+        loop {
+            self.take_while(char::is_whitespace);
+            if self.peek().is_none() {
+                break;
+            }
+            terms.push(self.term()?);
+        }
+
+        Ok(Query { terms })
+    }
+
+    fn term(&mut self) -> Result<Term, ParseError> {
+        let start = self.pos;
+
+        let negated = self.peek() == Some('-');
+        if negated {
+            self.bump();
+            if self.at_end_of_term() {
+                return Err(self.error(start, "Expected something to exclude after '-'"));
+            }
+        }
+
+        if self.peek() == Some('"') {
+            return Ok(Term {
+                negated,
+                predicate: Predicate::Text(self.quoted()?),
+            });
+        }
+
+        let field_start = self.pos;
+        let word = self.take_while(|c| !c.is_whitespace() && c != ':' && c != '"');
+
+        if self.peek() != Some(':') {
+            return Ok(Term {
+                negated,
+                predicate: Predicate::Text(word.to_string()),
+            });
+        }
+
+        self.bump();
+
+        let predicate = match word.to_lowercase().as_str() {
+            "artist" | "user" => Predicate::Artist(self.value(word)?),
+            "title" => Predicate::Title(self.value(word)?),
+            "genre" => Predicate::Genre(self.value(word)?),
+            "duration" | "length" => {
+                let cmp = self.comparison();
+                let start = self.pos;
+                let value = self.value(word)?;
+                let ms = parse_duration(&value).map_err(|message| self.error(start, message))?;
+                Predicate::Duration(cmp, ms)
+            }
+            "liked" | "added" => {
+                let cmp = self.comparison();
+                let start = self.pos;
+                let value = self.value(word)?;
+                if !is_date(&value) {
+                    return Err(self.error(
+                        start,
+                        format!("Expected a date like 2024, 2024-01 or 2024-01-01, not '{value}'"),
+                    ));
+                }
+                Predicate::Added(cmp, value)
+            }
+            field @ ("plays" | "likes") => {
+                let cmp = self.comparison();
+                let start = self.pos;
+                let value = self.value(word)?;
+                let n = parse_count(&value).ok_or_else(|| {
+                    self.error(
+                        start,
+                        format!("Expected a number like 500, 10k or 1.5m, not '{value}'"),
+                    )
+                })?;
+                if field == "plays" {
+                    Predicate::Plays(cmp, n)
+                } else {
+                    Predicate::Likes(cmp, n)
+                }
+            }
+            _ => {
+                return Err(self.error(
+                    field_start,
+                    format!("Unknown field '{word}', expected one of {FIELDS}"),
+                ))
+            }
+        };
+
+        Ok(Term { negated, predicate })
+    }
+
+    /// A quoted string, without the quotes
+    fn quoted(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.bump();
+
+        let text = self.take_while(|c| c != '"');
+        if self.bump() != Some('"') {
+            return Err(self.error(start, "Missing closing '\"'"));
+        }
+        if text.is_empty() {
+            return Err(self.error(start, "Empty quotes"));
+        }
+
+        Ok(text.to_string())
+    }
+
+    /// The value of a field, either quoted or up to the next whitespace
+    fn value(&mut self, field: &str) -> Result<String, ParseError> {
+        if self.peek() == Some('"') {
+            return self.quoted();
+        }
+
+        let start = self.pos;
+        let value = self.take_while(|c| !c.is_whitespace());
+        if value.is_empty() {
+            return Err(self.error(start, format!("Expected a value after '{field}:'")));
+        }
+
+        Ok(value.to_string())
+    }
+
+    fn comparison(&mut self) -> Comparison {
+        let rest = &self.input[self.pos..];
+        let (cmp, len) = if rest.starts_with(">=") {
+            (Comparison::GreaterEq, 2)
+        } else if rest.starts_with("<=") {
+            (Comparison::LessEq, 2)
+        } else if rest.starts_with('>') {
+            (Comparison::Greater, 1)
+        } else if rest.starts_with('<') {
+            (Comparison::Less, 1)
+        } else if rest.starts_with('=') {
+            (Comparison::Eq, 1)
+        } else {
+            (Comparison::Eq, 0)
+        };
+
+        self.pos += len;
+        cmp
+    }
+}
+
+/// Parse 90s, 10m, 1h30m, 3:30 or 1:02:03 into milliseconds. A plain number is minutes.
+fn parse_duration(s: &str) -> Result<usize, String> {
+    let invalid = || format!("Expected a duration like 90s, 10m, 1h30m or 3:30, not '{s}'");
+    let too_long = || format!("Duration '{s}' is too long");
+
+    // NOTE(emily): Numbers that dont fit are too long rather than invalid
+    let number = |digits: &str| match digits.parse::<usize>() {
+        Ok(n) => Ok(n),
+        Err(err) if matches!(err.kind(), std::num::IntErrorKind::PosOverflow) => Err(too_long()),
+        Err(_) => Err(invalid()),
+    };
+
+    let secs = if s.contains(':') {
+        let mut secs: usize = 0;
+        for part in s.split(':') {
+            let n = number(part)?;
+            secs = secs
+                .checked_mul(60)
+                .and_then(|secs| secs.checked_add(n))
+                .ok_or_else(too_long)?;
+        }
+        secs
+    } else if s.bytes().all(|b| b.is_ascii_digit()) {
+        number(s)?.checked_mul(60).ok_or_else(too_long)?
+    } else {
+        let mut secs: usize = 0;
+        let mut rest = s;
+        while !rest.is_empty() {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or_else(invalid)?;
+            let n = number(&rest[..digits])?;
+            let unit = match rest[digits..].chars().next() {
+                Some('h') => 3600,
+                Some('m') => 60,
+                Some('s') => 1,
+                _ => return Err(invalid()),
+            };
+            secs = n
+                .checked_mul(unit)
+                .and_then(|n| secs.checked_add(n))
+                .ok_or_else(too_long)?;
+            rest = &rest[digits + 1..];
+        }
+        secs
+    };
+
+    secs.checked_mul(1000).ok_or_else(too_long)
+}
+
+/// YYYY, YYYY-MM or YYYY-MM-DD
+fn is_date(s: &str) -> bool {
+    matches!(s.len(), 4 | 7 | 10)
+        && s.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+/// Parse 500, 10k or 1.5m
+fn parse_count(s: &str) -> Option<u64> {
+    let lower = s.to_lowercase();
+    let (number, multiplier) = if let Some(number) = lower.strip_suffix('k') {
+        (number, 1_000.0)
+    } else if let Some(number) = lower.strip_suffix('m') {
+        (number, 1_000_000.0)
+    } else {
+        (lower.as_str(), 1.0)
+    };
+
+    let n: f64 = number.parse().ok()?;
+    (n >= 0.0).then(|| (n * multiplier) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn predicate(input: &str) -> Predicate {
+        let mut query = Query::parse(input).unwrap();
+        assert_eq!(query.terms.len(), 1);
+        query.terms.remove(0).predicate
+    }
+
+    fn error(input: &str) -> ParseError {
+        Query::parse(input).unwrap_err()
+    }
+
+    #[test]
+    fn text_and_fields() {
+        let query = Query::parse(r#"foo -"bar baz" artist:emily title:"a b""#).unwrap();
+        assert_eq!(
+            query.terms,
+            vec![
+                Term {
+                    negated: false,
+                    predicate: Predicate::Text("foo".into()),
+                },
+                Term {
+                    negated: true,
+                    predicate: Predicate::Text("bar baz".into()),
+                },
+                Term {
+                    negated: false,
+                    predicate: Predicate::Artist("emily".into()),
+                },
+                Term {
+                    negated: false,
+                    predicate: Predicate::Title("a b".into()),
+                },
+            ]
+        );
+        assert!(Query::parse("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn comparisons() {
+        for (input, cmp) in [
+            ("plays:<5", Comparison::Less),
+            ("plays:<=5", Comparison::LessEq),
+            ("plays:=5", Comparison::Eq),
+            ("plays:5", Comparison::Eq),
+            ("plays:>=5", Comparison::GreaterEq),
+            ("plays:>5", Comparison::Greater),
+        ] {
+            assert_eq!(predicate(input), Predicate::Plays(cmp, 5), "{input}");
+        }
+    }
+
+    #[test]
+    fn durations() {
+        for (input, secs) in [
+            ("90s", 90),
+            ("10m", 600),
+            ("1h30m", 5400),
+            ("1h2m3s", 3723),
+            ("3:30", 210),
+            ("1:02:03", 3723),
+            ("4", 240),
+        ] {
+            assert_eq!(parse_duration(input), Ok(secs * 1000), "{input}");
+        }
+
+        assert_eq!(
+            predicate("duration:>10m"),
+            Predicate::Duration(Comparison::Greater, 600_000)
+        );
+    }
+
+    #[test]
+    fn counts_and_dates() {
+        assert_eq!(
+            predicate("likes:>50k"),
+            Predicate::Likes(Comparison::Greater, 50_000)
+        );
+        assert_eq!(
+            predicate("plays:1.5m"),
+            Predicate::Plays(Comparison::Eq, 1_500_000)
+        );
+        assert_eq!(
+            predicate("liked:<2024-01"),
+            Predicate::Added(Comparison::Less, "2024-01".into())
+        );
+    }
+
+    #[test]
+    fn invalid_durations() {
+        for input in ["", "10x", "m", "1h30", ":", "1::2", "-5m"] {
+            assert!(parse_duration(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn huge_durations_dont_overflow() {
+        for input in [
+            "99999999999999999999999",
+            "9999999999999999h",
+            "18446744073709551615s",
+            "9999999999999999999:00",
+            "1:2:3:4:5:6:7:8:9:10:11:12:13:14",
+        ] {
+            let err = parse_duration(input).unwrap_err();
+            assert!(err.contains("too long"), "{input}: {err}");
+        }
+
+        let err = error("duration:>99999999999999999999h");
+        assert_eq!(err.position, 10);
+        assert!(err.message.contains("too long"));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("foo -").position, 4);
+        assert_eq!(error(r#"title:"abc"#).position, 6);
+        assert_eq!(error("bpm:120").position, 0);
+        assert_eq!(error("genre:").position, 6);
+        assert_eq!(error("liked:yesterday").position, 6);
+        assert_eq!(error("plays:lots").position, 6);
+        assert_eq!(error("duration:10x").position, 9);
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use iced::widget;
+use iced::Element;
+use iced::Length;
+
+use crate::library::LibraryResults;
+
+use super::app::Message;
+
+/// Results of searching everything that we have loaded so far, grouped by type
+pub struct SearchPage {
+    pub query: String,
+    /// None until the search has finished
+    pub results: Option<Arc<LibraryResults>>,
+}
+
+impl SearchPage {
+    pub fn new(query: String) -> Self {
+        Self {
+            query,
+            results: None,
+        }
+    }
+
+    /// Results are only taken if they are for what this page is currently searching for
+    pub fn results_ready(&mut self, results: Arc<LibraryResults>) {
+        if results.query == self.query {
+            self.results = Some(results);
+        }
+    }
+
+    fn section<'a>(
+        title: &str,
+        count: usize,
+        rows: impl Iterator<Item = Element<'a, Message>>,
+    ) -> Element<'a, Message> {
+        rows.fold(
+            widget::column!(widget::text(format!("{title} ({count})")).size(24)).spacing(10),
+            |column, row| column.push(row),
+        )
+        .into()
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let header = widget::row!(
+            widget::text(format!("Library results for \"{}\"", self.query))
+                .size(40)
+                .width(Length::Fill),
+            widget::button(widget::text("Search SoundCloud"))
+                .on_press(Message::SoundCloudSearch(self.query.clone())),
+        )
+        .spacing(20)
+        .align_items(iced::Alignment::Center);
+
+        if self.query.trim().is_empty() {
+            return widget::column!(
+                header,
+                widget::text("Type something to search everything loaded so far")
+            )
+            .spacing(20)
+            .into();
+        }
+
+        let Some(results) = &self.results else {
+            return widget::column!(header, widget::text("Searching..."))
+                .spacing(20)
+                .into();
+        };
+
+        if results.is_empty() {
+            return widget::column!(
+                header,
+                widget::text("Nothing that has been loaded so far matches")
+            )
+            .spacing(20)
+            .into();
+        }
+
+        let songs = results.songs.iter().map(|song| {
+            let mut play = widget::button(widget::text("Play"));
+            let mut queue = widget::button(widget::text("Add to queue"));
+            if song.availability.is_playable() {
+                play = play.on_press(Message::SongPlay(song.clone()));
+                queue = queue.on_press(Message::SongQueue(song.clone()));
+            }
+
+            widget::row!(
+                widget::button(widget::text(&song.title))
+                    .style(iced::theme::Button::Text)
+                    .on_press(Message::SongClicked(song.clone()))
+                    .width(Length::Fill),
+                widget::button(widget::text(&song.user.username))
+                    .on_press(Message::UserClicked(song.user.clone())),
+                play,
+                queue,
+            )
+            .spacing(10)
+            .align_items(iced::Alignment::Center)
+            .into()
+        });
+
+        let users = results.users.iter().map(|user| {
+            widget::button(widget::text(&user.username))
+                .on_press(Message::UserClicked(user.clone()))
+                .into()
+        });
+
+        let playlists = results.playlists.iter().map(|playlist| {
+            widget::button(widget::text(format!(
+                "{} by {}",
+                playlist.title, playlist.user.username
+            )))
+            .on_press(Message::PlaylistClicked(playlist.clone()))
+            .into()
+        });
+
+        widget::column!(
+            header,
+            widget::scrollable(
+                widget::column!(
+                    Self::section("Songs", results.songs.len(), songs),
+                    Self::section("Users", results.users.len(), users),
+                    Self::section("Playlists", results.playlists.len(), playlists),
+                )
+                .spacing(40)
+            ),
+        )
+        .spacing(20)
+        .into()
+    }
+}
@@ -1,3 +1,4 @@
+use std::ops::Range;
 use std::sync::Arc;
 
 use fuzzy_matcher::skim::SkimMatcherV2;
@@ -10,47 +11,225 @@ use once_cell::sync::OnceCell;
 use crate::model;
 
 use super::app::Message;
+use super::song_list::ROW_HEIGHT;
 
 use iced::widget::Component;
 
 static MATCHER: OnceCell<SkimMatcherV2> = OnceCell::new();
 
+fn fuzzy_indices(choice: &str, pattern: &str) -> Option<(i64, Vec<usize>)> {
+    let matcher = MATCHER.get_or_init(|| SkimMatcherV2::default());
+    matcher.fuzzy_indices(choice, pattern)
+}
+
+/// Merge sorted character indices into ranges of consecutive characters
+fn to_ranges(mut indices: Vec<usize>) -> Vec<Range<usize>> {
+    indices.sort_unstable();
+
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for i in indices {
+        match ranges.last_mut() {
+            Some(range) if range.end == i => range.end += 1,
+            Some(range) if range.contains(&i) => {}
+            _ => ranges.push(i..i + 1),
+        }
+    }
+    ranges
+}
+
+/// Character ranges of the title and username that matched the filter
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct Highlights {
+    pub title: Vec<Range<usize>>,
+    pub username: Vec<Range<usize>>,
+}
+
+impl Highlights {
+    pub fn extend(&mut self, other: Highlights) {
+        self.title.extend(other.title);
+        self.username.extend(other.username);
+    }
+
+    /// Sort and merge overlapping ranges
+    pub fn normalize(&mut self) {
+        for ranges in [&mut self.title, &mut self.username] {
+            ranges.sort_by_key(|range| range.start);
+
+            let mut merged: Vec<Range<usize>> = vec![];
+            for range in ranges.drain(..) {
+                match merged.last_mut() {
+                    Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                    _ => merged.push(range),
+                }
+            }
+            *ranges = merged;
+        }
+    }
+}
+
+/// text, with the characters in ranges picked out in HIGHLIGHT_COLOR
+fn highlighted<'a>(
+    text: &str,
+    ranges: &[Range<usize>],
+    color: Option<iced::Color>,
+) -> Element<'a, Message> {
+    let styled = |segment: String, highlight: bool| {
+        let text = widget::text(segment);
+        match (highlight, color) {
+            (true, _) => text.style(HIGHLIGHT_COLOR),
+            (false, Some(color)) => text.style(color),
+            (false, None) => text,
+        }
+    };
+
+    if ranges.is_empty() {
+        return styled(text.to_string(), false).into();
+    }
+
+    let mut row = widget::row!();
+    let mut segment = String::new();
+    let mut segment_highlighted = false;
+
+    for (i, c) in text.chars().enumerate() {
+        let highlight = ranges.iter().any(|range| range.contains(&i));
+        if highlight != segment_highlighted && !segment.is_empty() {
+            row = row.push(styled(std::mem::take(&mut segment), segment_highlighted));
+        }
+        segment_highlighted = highlight;
+        segment.push(c);
+    }
+
+    if !segment.is_empty() {
+        row = row.push(styled(segment, segment_highlighted));
+    }
+
+    row.into()
+}
+
+const UNAVAILABLE_COLOR: iced::Color = iced::Color::from_rgb(0.5, 0.5, 0.5);
+const HIGHLIGHT_COLOR: iced::Color = iced::Color::from_rgb(1.0, 0.55, 0.0);
+
+/// Width of each of the duration / added / plays / likes columns
+pub const STAT_WIDTH: f32 = 90.0;
+pub const BUTTONS_WIDTH: f32 = 130.0;
+
+/// Milliseconds as h:mm:ss, or m:ss for anything under an hour
+pub(crate) fn format_duration(ms: usize) -> String {
+    let secs = ms / 1000;
+    let (hours, mins, secs) = (secs / 3600, (secs / 60) % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{mins:02}:{secs:02}")
+    } else {
+        format!("{mins}:{secs:02}")
+    }
+}
+
+/// Counts as e.g. 950, 12.3K, 4.5M
+pub(crate) fn format_count(count: Option<u64>) -> String {
+    match count {
+        None => "-".into(),
+        Some(n) if n < 1_000 => n.to_string(),
+        Some(n) if n < 1_000_000 => format!("{:.1}K", n as f64 / 1_000.0),
+        Some(n) => format!("{:.1}M", n as f64 / 1_000_000.0),
+    }
+}
+
+fn stat<'a>(text: String) -> Element<'a, Message> {
+    widget::text(text)
+        .size(14)
+        .width(Length::Fixed(STAT_WIDTH))
+        .into()
+}
+
 #[derive(Clone)]
 pub struct Song {
     song: Arc<model::Song>,
+    /// Position of this song in the list that it belongs to
+    index: usize,
+    /// ISO 8601 timestamp of when the song was added to that list
+    added_at: Option<String>,
 }
 
 impl Song {
-    pub fn view(&self) -> Element<Message> {
+    pub fn view(&self, highlights: &Highlights) -> Element<Message> {
+        let playable = self.song.availability.is_playable();
+
+        let mut title = widget::column!().push(
+            widget::button(highlighted(
+                &self.song.title,
+                &highlights.title,
+                (!playable).then_some(UNAVAILABLE_COLOR),
+            ))
+            .padding(0)
+            .style(iced::theme::Button::Text)
+            .on_press(Message::SongClicked(self.song.clone())),
+        );
+
+        if self.song.availability != model::Availability::Available {
+            title = title.push(
+                widget::text(self.song.availability.description())
+                    .size(12)
+                    .style(UNAVAILABLE_COLOR),
+            );
+        }
+
+        let mut play = widget::button(widget::text("Play"));
+        let mut queue = widget::button(widget::text("Add to queue"));
+        let mut play_from_here = widget::button(widget::text("Play from here"));
+        let mut station = widget::button(widget::text("Start station"));
+        if playable {
+            play = play.on_press(Message::SongPlay(self.song.clone()));
+            queue = queue.on_press(Message::SongQueue(self.song.clone()));
+            play_from_here = play_from_here.on_press(Message::PlayFromHere(self.index));
+            station = station.on_press(Message::StartStation(self.song.clone()));
+        }
+
         {
-            if let Some(image) = &self.song.artwork {
-                widget::row!(
-                    widget::image::Image::new(image.as_ref().clone()).width(Length::Fixed(150.0))
-                )
+            if let Some(image) = self.song.artwork.maybe() {
+                widget::row!(widget::image::Image::new(image.as_ref().clone())
+                    .width(Length::Fixed(ROW_HEIGHT))
+                    .height(Length::Fixed(ROW_HEIGHT)))
             } else {
-                widget::row!()
+                // NOTE(emily): Keep the space so that the columns still line up with the header
+                widget::row!(widget::Space::with_width(Length::Fixed(ROW_HEIGHT)))
             }
             .push(
                 widget::row!()
                     .push(
                         widget::column!()
-                            .push(widget::text(&self.song.title))
+                            .push(title)
                             .push(
-                                widget::button(widget::text(self.song.user.username.clone()))
-                                    .on_press(Message::UserClicked(self.song.user.clone())),
+                                widget::button(highlighted(
+                                    &self.song.user.username,
+                                    &highlights.username,
+                                    None,
+                                ))
+                                .on_press(Message::UserClicked(self.song.user.clone())),
                             )
                             .spacing(20)
                             .width(Length::Shrink),
                     )
                     .width(Length::Fill)
                     .spacing(20)
+                    .push(stat(format_duration(self.song.full_duration)))
+                    .push(stat(
+                        // NOTE(emily): Just the date part of the timestamp
+                        self.added_at
+                            .as_deref()
+                            .map_or("-", |added| added.get(..10).unwrap_or(added))
+                            .to_string(),
+                    ))
+                    .push(stat(format_count(self.song.plays)))
+                    .push(stat(format_count(self.song.likes)))
                     .push(
-                        widget::button(widget::text("Add to queue"))
-                            .on_press(Message::SongQueue(self.song.clone())),
+                        widget::column!(play, queue, play_from_here, station)
+                            .spacing(5)
+                            .width(Length::Fixed(BUTTONS_WIDTH)),
                     ),
             )
         }
         .spacing(20)
+        .height(Length::Fixed(ROW_HEIGHT))
         .into()
     }
 
@@ -58,6 +237,14 @@ impl Song {
         &self.song
     }
 
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn added_at(&self) -> Option<&str> {
+        self.added_at.as_deref()
+    }
+
     pub fn title(&self) -> &str {
         &self.song.title
     }
@@ -74,20 +261,47 @@ impl Song {
         self.song.user.username.as_ref()
     }
 
-    pub fn match_score(&self, pattern: &str) -> Option<i64> {
-        let matcher = MATCHER.get_or_init(|| SkimMatcherV2::default());
-        let title_score = matcher.fuzzy_match(&self.song.title, pattern);
-        let username_score = matcher.fuzzy_match(&self.song.user.username, pattern);
-        if title_score.is_none() && username_score.is_none() {
-            None
-        } else {
-            Some(title_score.unwrap_or_default() + username_score.unwrap_or_default())
+    pub fn match_title(&self, pattern: &str) -> Option<(i64, Highlights)> {
+        let (score, indices) = fuzzy_indices(&self.song.title, pattern)?;
+        Some((
+            score,
+            Highlights {
+                title: to_ranges(indices),
+                ..Default::default()
+            },
+        ))
+    }
+
+    pub fn match_username(&self, pattern: &str) -> Option<(i64, Highlights)> {
+        let (score, indices) = fuzzy_indices(&self.song.user.username, pattern)?;
+        Some((
+            score,
+            Highlights {
+                username: to_ranges(indices),
+                ..Default::default()
+            },
+        ))
+    }
+
+    pub fn match_score(&self, pattern: &str) -> Option<(i64, Highlights)> {
+        match (self.match_title(pattern), self.match_username(pattern)) {
+            (None, None) => None,
+            (title, username) => {
+                let (title_score, mut highlights) = title.unwrap_or_default();
+                let (username_score, username_highlights) = username.unwrap_or_default();
+                highlights.extend(username_highlights);
+                Some((title_score + username_score, highlights))
+            }
         }
     }
 }
 
 impl Song {
-    pub fn new(song: Arc<model::Song>) -> Self {
-        Self { song }
+    pub fn new(song: Arc<model::Song>, index: usize, added_at: Option<String>) -> Self {
+        Self {
+            song,
+            index,
+            added_at,
+        }
     }
 }
@@ -1,146 +1,387 @@
-use std::{collections::HashMap, sync::Arc};
+use std::cmp;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+use std::time::Duration;
 
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
-use iced::widget;
+use iced::widget::{self, scrollable};
 use iced::Command;
 use iced::Element;
+use iced::Length;
+use log::{debug, warn};
 
 use crate::model;
 
 use iced::widget::Component;
 
-use super::{app::Message, song::Song};
+use super::{
+    app::Message,
+    query::Query,
+    song::{Highlights, Song},
+};
 
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub enum Display {
-    Show(i64),
+    /// Score and what matched
+    Show(i64, Highlights),
     Hidden,
 }
 
 impl Default for Display {
     fn default() -> Self {
-        Self::Show(100)
+        Self::Show(100, Highlights::default())
     }
 }
 
-pub struct SongHolder {
-    song: Song,
-    display: Display,
+/// How long the filter has to stay the same before we start computing it
+const FILTER_DEBOUNCE: Duration = Duration::from_millis(150);
+/// How many songs are scored between checks for cancellation
+const FILTER_CANCEL_CHECK: usize = 256;
+
+/// Shared between all song lists so that results can never be mistaken for another lists
+static FILTER_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Score candidates (or every song) against query. Returns None if cancelled part way through.
+fn compute_filter(
+    songs: &[Song],
+    query: &Query,
+    candidates: Option<Vec<usize>>,
+    cancel: &AtomicBool,
+) -> Option<Vec<Display>> {
+    let mut displays = vec![Display::Hidden; songs.len()];
+    let candidates = candidates.unwrap_or_else(|| (0..songs.len()).collect());
+
+    for (n, i) in candidates.into_iter().enumerate() {
+        if n % FILTER_CANCEL_CHECK == 0 && cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        if let Some((score, highlights)) = query.score(&songs[i]) {
+            displays[i] = Display::Show(score, highlights);
+        }
+    }
+
+    Some(displays)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortColumn {
+    Title,
+    Artist,
+    Duration,
+    /// When the song was added to the list (liked, reposted, uploaded...)
+    Added,
+    Plays,
+    Likes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort {
+    pub column: SortColumn,
+    pub ascending: bool,
+}
+
+impl Sort {
+    fn compare(&self, a: &Song, b: &Song) -> cmp::Ordering {
+        let ordering = match self.column {
+            SortColumn::Title => a.title().to_lowercase().cmp(&b.title().to_lowercase()),
+            SortColumn::Artist => a
+                .username()
+                .to_lowercase()
+                .cmp(&b.username().to_lowercase()),
+            SortColumn::Duration => a.model().full_duration.cmp(&b.model().full_duration),
+            // NOTE(emily): ISO 8601 timestamps sort the same as the dates they represent
+            SortColumn::Added => a.added_at().cmp(&b.added_at()),
+            SortColumn::Plays => a.model().plays.cmp(&b.model().plays),
+            SortColumn::Likes => a.model().likes.cmp(&b.model().likes),
+        };
+
+        if self.ascending {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    }
 }
 
+/// Height of a single song row, the song views are fixed to this
+pub const ROW_HEIGHT: f32 = 150.0;
+const ROW_SPACING: f32 = 20.0;
+const ROW_STRIDE: f32 = ROW_HEIGHT + ROW_SPACING;
+/// How many rows either side of the viewport to build as well
+const OVERSCAN: usize = 3;
+/// Used until the scrollable tells us how big it actually is
+const DEFAULT_VIEWPORT_HEIGHT: f32 = 1080.0;
+
 pub struct SongList {
-    song_list: HashMap<model::Id, SongHolder>,
+    /// Songs in playlist order. Each Song knows its index into this.
+    /// Shared with filter computations so that they dont need to copy it.
+    song_list: Arc<Vec<Song>>,
+    /// Whether and how each song in song_list is shown
+    displays: Vec<Display>,
+    /// Indices into song_list of the songs that are shown, in the order they are shown
+    displayed: Vec<usize>,
+    /// Generation of the most recent filter request, results for any other are stale
+    filter_generation: u64,
+    /// Set to stop the most recent filter computation
+    filter_cancel: Arc<AtomicBool>,
+    /// The query that displays was computed from
+    applied_query: Query,
+    /// The query that is being computed, and its generation
+    pending_query: Option<(u64, Query)>,
+    /// None keeps playlist order (or filter ranking when filtering)
+    sort: Option<Sort>,
     playlist: Arc<model::Playlist>,
-    scroll_pos: f32,
+    scroll_id: scrollable::Id,
+    /// Pixels from the top of the list
+    scroll_offset: f32,
+    viewport_height: f32,
 }
 
 impl SongList {
     pub fn new(playlist: Arc<model::Playlist>) -> Self {
-        Self {
-            song_list: HashMap::from_iter(playlist.songs.iter().map(|song| {
-                (
-                    song.id,
-                    SongHolder {
-                        song: Song::new(song.clone()),
-                        display: Display::default(),
-                    },
-                )
-            })),
+        let mut zelf = Self {
+            song_list: Arc::new(
+                playlist
+                    .songs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, song)| {
+                        Song::new(
+                            song.clone(),
+                            index,
+                            playlist.added_at.get(index).cloned().flatten(),
+                        )
+                    })
+                    .collect(),
+            ),
+            displays: vec![Display::default(); playlist.songs.len()],
+            displayed: vec![],
+            filter_generation: 0,
+            filter_cancel: Default::default(),
+            applied_query: Query::default(),
+            pending_query: None,
+            sort: None,
             playlist,
-            scroll_pos: 0.0,
-        }
+            scroll_id: scrollable::Id::unique(),
+            scroll_offset: 0.0,
+            viewport_height: DEFAULT_VIEWPORT_HEIGHT,
+        };
+
+        zelf.update_displayed();
+        zelf.request_visible_artwork();
+        zelf
     }
 
-    pub fn view(&self) -> Element<Message> {
-        let mut column = widget::column!();
-
-        // TODO(emily): Ideally, dont collect here, only to throw it away.
-        // We collect right now to determine how many songs are actually going to be displayed
-        // in total.
-        let mut displayed_songs: Vec<&SongHolder> = self
-            .song_list
-            .values()
-            .filter_map(|song| {
-                if let Display::Hidden = song.display {
-                    None
-                } else {
-                    Some(song)
-                }
+    fn update_displayed(&mut self) {
+        let mut displayed: Vec<(i64, usize)> = self
+            .displays
+            .iter()
+            .enumerate()
+            .filter_map(|(i, display)| match display {
+                Display::Show(score, _) => Some((*score, i)),
+                Display::Hidden => None,
             })
             .collect();
 
-        displayed_songs.sort_by_cached_key(|song| {
-            // Safety: All Display::Hidden songs are removed beforehand
-            let Display::Show(score) = song.display else { unsafe { std::hint::unreachable_unchecked()} };
-            0 - score
+        // Sorted by the sort column first, then by how well the song matches the filter.
+        // Songs that are equal on both stay in playlist order.
+        displayed.sort_by(|(a_score, a), (b_score, b)| {
+            self.sort
+                .map_or(cmp::Ordering::Equal, |sort| {
+                    sort.compare(&self.song_list[*a], &self.song_list[*b])
+                })
+                .then(b_score.cmp(a_score))
+                .then(a.cmp(b))
         });
 
-        let total_len = displayed_songs.len() as f32;
-
-        for song in displayed_songs.into_iter().enumerate().map(|(i, song)| {
-            let song_pos = i as f32 / total_len as f32;
-            // TODO(emily): This '10' needs to have some relation to how many things
-            // can actually fit on the screen.
-            // for now just scale with how many things exist in the list
-            if (song_pos - self.scroll_pos).abs() < (10 as f32 / total_len as f32) {
-                Some(song)
-            } else {
-                None
-            }
-        }) {
-            match song {
-                Some(song) => column = column.push(song.song.view()),
-                // TODO(emily): Get this height from the song element
-                None => column = column.push(widget::column!().height(150.0)),
-            };
-        }
+        self.displayed = displayed.into_iter().map(|(_, i)| i).collect();
+    }
 
-        widget::scrollable(column.spacing(20))
-            .on_scroll(|ro| Message::PageScroll(ro.relative_offset().y))
-            .into()
+    /// Sort by column. Picking the current column again reverses it, then goes back to
+    /// playlist order.
+    pub(crate) fn sort_by(&mut self, column: SortColumn) -> Command<Message> {
+        self.sort = match self.sort {
+            Some(sort) if sort.column == column && sort.ascending => Some(Sort {
+                column,
+                ascending: false,
+            }),
+            Some(sort) if sort.column == column => None,
+            // NOTE(emily): Numbers and dates are most useful biggest / newest first
+            _ => Some(Sort {
+                column,
+                ascending: matches!(column, SortColumn::Title | SortColumn::Artist),
+            }),
+        };
+
+        self.update_displayed();
+
+        self.scroll_offset = 0.0;
+        self.request_visible_artwork();
+        scrollable::scroll_to(
+            self.scroll_id.clone(),
+            scrollable::AbsoluteOffset::default(),
+        )
     }
 
-    pub fn update_filter(&mut self, str: &str) -> Command<Message> {
-        let str = str.to_owned();
+    fn header_button(&self, label: &str, column: SortColumn) -> widget::Button<'_, Message> {
+        let label = match self.sort {
+            Some(sort) if sort.column == column && sort.ascending => format!("{label} ▲"),
+            Some(sort) if sort.column == column => format!("{label} ▼"),
+            _ => label.to_string(),
+        };
 
-        let song_list: Vec<Song> = self
-            .song_list
-            .iter()
-            .map(|(id, holder)| holder.song.clone())
-            .collect();
+        widget::button(widget::text(label).size(14))
+            .style(iced::theme::Button::Text)
+            .on_press(Message::SongListSort(column))
+    }
 
-        if str.len() < 1 {
-            Command::perform(
-                async move {
-                    song_list
-                        .iter()
-                        .map(|song| (song.model().id, Display::default()))
-                        .collect()
-                },
-                Message::SongListFilterComputed,
-            )
-        } else {
-            Command::perform(
-                async move {
-                    song_list
-                        .iter()
-                        .map(|song| {
-                            (song.model().id, {
-                                match song.match_score(&str) {
-                                    Some(score) => Display::Show(score),
-                                    None => Display::Hidden,
-                                }
-                            })
-                        })
-                        .collect()
-                },
-                Message::SongListFilterComputed,
+    fn header(&self) -> Element<Message> {
+        use super::song::{BUTTONS_WIDTH, STAT_WIDTH};
+
+        widget::row!(
+            widget::Space::with_width(Length::Fixed(ROW_HEIGHT)),
+            widget::row!(
+                self.header_button("Title", SortColumn::Title),
+                widget::text("/"),
+                self.header_button("Artist", SortColumn::Artist),
             )
+            .align_items(iced::Alignment::Center)
+            .width(Length::Fill),
+            self.header_button("Duration", SortColumn::Duration)
+                .width(Length::Fixed(STAT_WIDTH)),
+            self.header_button("Added", SortColumn::Added)
+                .width(Length::Fixed(STAT_WIDTH)),
+            self.header_button("Plays", SortColumn::Plays)
+                .width(Length::Fixed(STAT_WIDTH)),
+            self.header_button("Likes", SortColumn::Likes)
+                .width(Length::Fixed(STAT_WIDTH)),
+            widget::Space::with_width(Length::Fixed(BUTTONS_WIDTH)),
+        )
+        .spacing(20)
+        .into()
+    }
+
+    /// Range of displayed rows that need building for the current viewport
+    fn visible_rows(&self) -> std::ops::Range<usize> {
+        let total = self.displayed.len();
+        let first = ((self.scroll_offset / ROW_STRIDE).floor() as usize)
+            .saturating_sub(OVERSCAN)
+            .min(total);
+        let last = (((self.scroll_offset + self.viewport_height) / ROW_STRIDE).ceil() as usize
+            + OVERSCAN)
+            .min(total);
+
+        first..last
+    }
+
+    /// Start loading artwork for the rows that are (nearly) on screen. Everything else
+    /// is left until it is scrolled to, big playlists have thousands of songs.
+    fn request_visible_artwork(&self) {
+        for &i in &self.displayed[self.visible_rows()] {
+            self.song_list[i].song().artwork.request();
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let rows = self.visible_rows();
+        let total = self.displayed.len();
+
+        // Everything outside of the visible rows is replaced by space of the same height,
+        // so that the scrollable still knows how big the whole list is
+        let mut column = widget::column!().push(widget::Space::with_height(Length::Fixed(
+            rows.start as f32 * ROW_STRIDE,
+        )));
+
+        for &i in &self.displayed[rows.clone()] {
+            let highlights = match &self.displays[i] {
+                Display::Show(_, highlights) => highlights,
+                Display::Hidden => continue,
+            };
+
+            column = column.push(
+                widget::container(self.song_list[i].view(highlights))
+                    .height(Length::Fixed(ROW_STRIDE))
+                    .width(Length::Fill),
+            );
         }
+
+        column = column.push(widget::Space::with_height(Length::Fixed(
+            (total - rows.end) as f32 * ROW_STRIDE,
+        )));
+
+        widget::column!(
+            self.header(),
+            widget::scrollable(column)
+                .id(self.scroll_id.clone())
+                .on_scroll(Message::PageScroll),
+        )
+        .spacing(10)
+        .into()
     }
 
+    /// Start filtering with query. The filter is computed in the background once the query
+    /// has settled, and anything still computing for a previous query is cancelled.
+    pub fn update_filter(&mut self, query: Query) -> Command<Message> {
+        self.filter_cancel.store(true, Ordering::Relaxed);
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.filter_cancel = cancel.clone();
+
+        let generation = FILTER_GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+        self.filter_generation = generation;
+
+        // When the query only got more specific, only the songs that are already shown
+        // can still match
+        let candidates = query.narrows(&self.applied_query).then(|| {
+            self.displays
+                .iter()
+                .enumerate()
+                .filter(|(_, display)| **display != Display::Hidden)
+                .map(|(i, _)| i)
+                .collect()
+        });
+
+        self.pending_query = Some((generation, query.clone()));
+        let song_list = self.song_list.clone();
+
+        Command::perform(
+            async move {
+                tokio::time::sleep(FILTER_DEBOUNCE).await;
+                if cancel.load(Ordering::Relaxed) {
+                    return None;
+                }
+
+                tokio::task::spawn_blocking(move || {
+                    compute_filter(&song_list, &query, candidates, &cancel)
+                })
+                .await
+                .ok()
+                .flatten()
+            },
+            move |computed| match computed {
+                Some(computed) => Message::SongListFilterComputed(generation, computed),
+                None => Message::none(),
+            },
+        )
+    }
+
+    /// Songs in playlist order
     pub fn models(&self) -> impl Iterator<Item = &'_ Song> {
-        self.song_list.values().map(|h| &h.song)
+        self.song_list.iter()
+    }
+
+    /// Songs in the order that they are shown, from the song at index in playlist order
+    /// onwards. Anything that is filtered out is left out.
+    pub fn displayed_from(&self, index: usize) -> impl Iterator<Item = &'_ Song> {
+        let start = self
+            .displayed
+            .iter()
+            .position(|&i| i == index)
+            .unwrap_or(self.displayed.len());
+
+        self.displayed[start..].iter().map(|&i| &self.song_list[i])
     }
 
     pub fn playlist(&self) -> &Arc<model::Playlist> {
@@ -151,17 +392,46 @@ impl SongList {
         self.playlist.title.as_str()
     }
 
+    /// computed has one Display for each song, in playlist order
     pub(crate) fn filter_computed(
         &mut self,
-        computed: &HashMap<model::Id, Display>,
+        generation: u64,
+        computed: Vec<Display>,
     ) -> Command<Message> {
-        for (k, v) in computed {
-            self.song_list
-                .get_mut(k)
-                .map(|holder| holder.display = v.clone());
+        if generation != self.filter_generation {
+            debug!("Dropping stale filter result {generation}");
+            return Command::none();
+        }
+
+        if computed.len() != self.song_list.len() {
+            warn!("Filter was computed for a different song list, ignoring");
+            return Command::none();
+        }
+
+        if let Some((_, query)) = self.pending_query.take() {
+            self.applied_query = query;
         }
 
-        Command::none()
+        // Remember which song was at the top so that we can keep it there if it is still shown
+        let top = (self.scroll_offset / ROW_STRIDE).floor() as usize;
+        let within_row = self.scroll_offset % ROW_STRIDE;
+        let anchor = self.displayed.get(top).copied();
+
+        self.displays = computed;
+        self.update_displayed();
+
+        self.scroll_offset = anchor
+            .and_then(|anchor| self.displayed.iter().position(|&i| i == anchor))
+            .map_or(0.0, |row| row as f32 * ROW_STRIDE + within_row);
+        self.request_visible_artwork();
+
+        scrollable::scroll_to(
+            self.scroll_id.clone(),
+            scrollable::AbsoluteOffset {
+                x: 0.0,
+                y: self.scroll_offset,
+            },
+        )
     }
 
     pub(crate) fn page_changed(&mut self, _amount: isize) {
@@ -173,8 +443,9 @@ impl SongList {
         // self.cur_page = new_amount as usize;
     }
 
-    pub(crate) fn page_scroll(&mut self, amount: f32) {
-        self.scroll_pos = amount;
-        // do something
+    pub(crate) fn page_scroll(&mut self, viewport: scrollable::Viewport) {
+        self.scroll_offset = viewport.absolute_offset().y;
+        self.viewport_height = viewport.bounds().height;
+        self.request_visible_artwork();
     }
 }
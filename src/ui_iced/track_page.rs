@@ -0,0 +1,285 @@
+use std::sync::Arc;
+
+use iced::widget;
+use iced::Element;
+use iced::Length;
+
+use crate::model;
+
+use super::app::Message;
+use super::song::{format_count, format_duration};
+
+const DIM_COLOR: iced::Color = iced::Color::from_rgb(0.6, 0.6, 0.6);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackTab {
+    Details,
+    Comments,
+}
+
+/// Everything we know about a single song
+pub struct TrackPage {
+    pub song: Arc<model::Song>,
+    artwork: model::Eventually<widget::image::Handle>,
+    /// Playlists that the song is in. None until they are loaded.
+    pub playlists: Option<Result<Arc<Vec<model::PlaylistSummary>>, String>>,
+    tab: TrackTab,
+    /// None until the comments tab is first opened and they load
+    pub comments: Option<Result<Arc<Vec<model::Comment>>, String>>,
+    /// Whether the comments have been asked for
+    comments_requested: bool,
+}
+
+fn detail<'a>(label: &str, value: impl ToString) -> Element<'a, Message> {
+    widget::row!(
+        widget::text(label)
+            .style(DIM_COLOR)
+            .width(Length::Fixed(100.0)),
+        widget::text(value),
+    )
+    .spacing(10)
+    .into()
+}
+
+impl TrackPage {
+    pub fn new(song: Arc<model::Song>, store: &model::Store) -> Self {
+        let artwork = store.lazy_image(song.artwork_url.as_deref(), model::ImageSize::Hero);
+        artwork.request();
+
+        Self {
+            artwork,
+            song,
+            playlists: None,
+            tab: TrackTab::Details,
+            comments: None,
+            comments_requested: false,
+        }
+    }
+
+    /// Returns whether the comments need loading
+    pub fn select_tab(&mut self, tab: TrackTab) -> bool {
+        self.tab = tab;
+
+        if tab == TrackTab::Comments && !self.comments_requested {
+            self.comments_requested = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn comments_resolved(
+        &mut self,
+        id: model::Id,
+        comments: Result<Arc<Vec<model::Comment>>, String>,
+    ) {
+        if id == self.song.id {
+            self.comments = Some(comments);
+        }
+    }
+
+    pub fn playlists_resolved(
+        &mut self,
+        id: model::Id,
+        playlists: Result<Arc<Vec<model::PlaylistSummary>>, String>,
+    ) {
+        if id == self.song.id {
+            self.playlists = Some(playlists);
+        }
+    }
+
+    fn header(&self) -> Element<Message> {
+        let song = &self.song;
+
+        let mut play = widget::button(widget::text("Play"));
+        let mut queue = widget::button(widget::text("Add to queue"));
+        let mut station = widget::button(widget::text("Start station"));
+        if song.availability.is_playable() {
+            play = play.on_press(Message::SongPlay(song.clone()));
+            queue = queue.on_press(Message::SongQueue(song.clone()));
+            station = station.on_press(Message::StartStation(song.clone()));
+        }
+
+        let mut info = widget::column!(
+            widget::text(&song.title).size(40),
+            widget::button(widget::text(&song.user.username))
+                .on_press(Message::UserClicked(song.user.clone())),
+            widget::row!(play, queue, station).spacing(10),
+        )
+        .spacing(20);
+
+        if song.availability != model::Availability::Available {
+            info = info.push(widget::text(song.availability.description()).style(DIM_COLOR));
+        }
+
+        if let Some(genre) = &song.genre {
+            info = info.push(widget::text(genre));
+        }
+
+        if !song.tags.is_empty() {
+            info = info.push(
+                widget::text(
+                    song.tags
+                        .iter()
+                        .map(|tag| format!("#{tag}"))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                )
+                .style(DIM_COLOR),
+            );
+        }
+
+        let artwork: Element<Message> = match self.artwork.maybe() {
+            Some(artwork) => widget::image::Image::new(artwork.as_ref().clone())
+                .width(Length::Fixed(300.0))
+                .height(Length::Fixed(300.0))
+                .into(),
+            None => widget::Space::new(Length::Fixed(300.0), Length::Fixed(300.0)).into(),
+        };
+
+        widget::row!(artwork, info).spacing(20).into()
+    }
+
+    fn details(&self) -> Element<Message> {
+        let song = &self.song;
+
+        let stats = widget::row!(
+            detail("Plays", format_count(song.plays)),
+            detail("Likes", format_count(song.likes)),
+            detail("Reposts", format_count(song.reposts)),
+            detail("Comments", format_count(song.comments)),
+        )
+        .spacing(40);
+
+        let mut details = widget::column!(
+            stats,
+            detail("Duration", format_duration(song.full_duration))
+        )
+        .spacing(10);
+
+        // NOTE(emily): Dates are shown without the time part
+        let optional = [
+            (
+                "Uploaded",
+                song.created_at.as_deref().map(|d| d.get(..10).unwrap_or(d)),
+            ),
+            (
+                "Released",
+                song.release_date
+                    .as_deref()
+                    .map(|d| d.get(..10).unwrap_or(d)),
+            ),
+            ("Label", song.label.as_deref()),
+            ("License", song.license.as_deref()),
+            ("Buy", song.purchase_url.as_deref()),
+            ("Link", song.permalink.as_deref()),
+        ];
+
+        for (label, value) in optional {
+            if let Some(value) = value {
+                details = details.push(detail(label, value));
+            }
+        }
+
+        details.into()
+    }
+
+    fn playlists(&self) -> Element<Message> {
+        let column = widget::column!(widget::text("In playlists").size(24)).spacing(10);
+
+        match &self.playlists {
+            None => column.push(widget::text("Loading...")),
+            Some(Err(err)) => column.push(widget::text(format!("Could not load playlists: {err}"))),
+            Some(Ok(playlists)) if playlists.is_empty() => {
+                column.push(widget::text("Not in any playlists"))
+            }
+            Some(Ok(playlists)) => playlists.iter().fold(column, |column, playlist| {
+                let label = match playlist.track_count {
+                    Some(count) => {
+                        format!(
+                            "{} by {} ({count} tracks)",
+                            playlist.title, playlist.username
+                        )
+                    }
+                    None => format!("{} by {}", playlist.title, playlist.username),
+                };
+                column.push(
+                    widget::button(widget::text(label))
+                        .on_press(Message::OpenPlaylist(playlist.id)),
+                )
+            }),
+        }
+        .into()
+    }
+
+    fn comments(&self) -> Element<Message> {
+        match &self.comments {
+            None => widget::text("Loading...").into(),
+            Some(Err(err)) => widget::text(format!("Could not load comments: {err}")).into(),
+            Some(Ok(comments)) if comments.is_empty() => widget::text("No comments").into(),
+            Some(Ok(comments)) => comments
+                .iter()
+                .fold(widget::column!().spacing(15), |column, comment| {
+                    let at = comment
+                        .timestamp
+                        .map(|t| format!(" at {}", format_duration(t)))
+                        .unwrap_or_default();
+
+                    column.push(
+                        widget::column!(
+                            widget::text(format!("{}{at}", comment.username))
+                                .size(14)
+                                .style(DIM_COLOR),
+                            widget::text(&comment.body),
+                        )
+                        .spacing(5),
+                    )
+                })
+                .into(),
+        }
+    }
+
+    fn tabs(&self) -> Element<Message> {
+        let tab = |label: String, target: TrackTab| {
+            let button = widget::button(widget::text(label));
+            if self.tab == target {
+                button
+            } else {
+                button
+                    .style(iced::theme::Button::Text)
+                    .on_press(Message::TrackTabChanged(target))
+            }
+        };
+
+        let comments = match self.song.comments {
+            Some(count) => format!("Comments ({})", format_count(Some(count))),
+            None => "Comments".to_string(),
+        };
+
+        widget::row!(
+            tab("Details".to_string(), TrackTab::Details),
+            tab(comments, TrackTab::Comments),
+        )
+        .spacing(10)
+        .into()
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let mut column = widget::column!(self.header(), self.tabs()).spacing(30);
+
+        match self.tab {
+            TrackTab::Details => {
+                column = column.push(self.details());
+
+                if let Some(description) = &self.song.description {
+                    column = column.push(widget::text(description));
+                }
+
+                column = column.push(self.playlists());
+            }
+            TrackTab::Comments => column = column.push(self.comments()),
+        }
+
+        widget::scrollable(column).into()
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use iced::widget;
+use iced::widget::scrollable;
+use iced::Element;
+use iced::Length;
+
+use crate::model;
+
+use super::app::Message;
+
+const COLUMNS: usize = 6;
+const AVATAR_SIZE: f32 = 100.0;
+const CARD_WIDTH: f32 = 150.0;
+/// Height of a row of cards, the cards are fixed to this
+const ROW_HEIGHT: f32 = 140.0;
+const ROW_SPACING: f32 = 20.0;
+const ROW_STRIDE: f32 = ROW_HEIGHT + ROW_SPACING;
+/// How many rows either side of the viewport to build as well
+const OVERSCAN: usize = 2;
+/// Used until the scrollable tells us how big it actually is
+const DEFAULT_VIEWPORT_HEIGHT: f32 = 1080.0;
+
+fn card(user: &Arc<model::User>) -> Element<'_, Message> {
+    // NOTE(emily): Avatars are requested once their row gets near the screen,
+    // they show up once they are done
+    let avatar: Element<Message> = match user.avatar.maybe() {
+        Some(avatar) => widget::image::Image::new(avatar.as_ref().clone())
+            .width(Length::Fixed(AVATAR_SIZE))
+            .height(Length::Fixed(AVATAR_SIZE))
+            .into(),
+        None => widget::Space::new(Length::Fixed(AVATAR_SIZE), Length::Fixed(AVATAR_SIZE)).into(),
+    };
+
+    widget::button(
+        widget::column!(avatar, widget::text(&user.username).size(14))
+            .spacing(5)
+            .align_items(iced::Alignment::Center)
+            .width(Length::Fixed(CARD_WIDTH)),
+    )
+    .style(iced::theme::Button::Text)
+    .on_press(Message::UserClicked(user.clone()))
+    .into()
+}
+
+/// Users laid out in rows, clicking on one opens their page.
+/// Like SongList only the rows that are (nearly) on screen get built.
+pub struct UserGrid {
+    users: Arc<Vec<Arc<model::User>>>,
+    /// Pixels from the top of the grid
+    scroll_offset: f32,
+    viewport_height: f32,
+}
+
+impl UserGrid {
+    pub fn new(users: Arc<Vec<Arc<model::User>>>) -> Self {
+        let zelf = Self {
+            users,
+            scroll_offset: 0.0,
+            viewport_height: DEFAULT_VIEWPORT_HEIGHT,
+        };
+
+        zelf.request_visible_avatars();
+        zelf
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    fn row_count(&self) -> usize {
+        (self.users.len() + COLUMNS - 1) / COLUMNS
+    }
+
+    /// Range of rows that need building for the current viewport
+    fn visible_rows(&self) -> std::ops::Range<usize> {
+        let total = self.row_count();
+        let first = ((self.scroll_offset / ROW_STRIDE).floor() as usize)
+            .saturating_sub(OVERSCAN)
+            .min(total);
+        let last = (((self.scroll_offset + self.viewport_height) / ROW_STRIDE).ceil() as usize
+            + OVERSCAN)
+            .min(total);
+
+        first..last
+    }
+
+    fn visible_users(&self) -> &[Arc<model::User>] {
+        let rows = self.visible_rows();
+        &self.users[rows.start * COLUMNS..(rows.end * COLUMNS).min(self.users.len())]
+    }
+
+    /// Start loading avatars for the rows that are (nearly) on screen. Users can have
+    /// up to a thousand followers, so everything else waits until it is scrolled to.
+    fn request_visible_avatars(&self) {
+        for user in self.visible_users() {
+            user.avatar.request();
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        let rows = self.visible_rows();
+
+        // Everything outside of the visible rows is replaced by space of the same height,
+        // so that the scrollable still knows how big the whole grid is
+        let mut column = widget::column!().push(widget::Space::with_height(Length::Fixed(
+            rows.start as f32 * ROW_STRIDE,
+        )));
+
+        for chunk in self.visible_users().chunks(COLUMNS) {
+            column = column.push(
+                widget::container(
+                    chunk
+                        .iter()
+                        .fold(widget::row!().spacing(20), |row, user| row.push(card(user))),
+                )
+                .height(Length::Fixed(ROW_STRIDE)),
+            );
+        }
+
+        column = column.push(widget::Space::with_height(Length::Fixed(
+            (self.row_count() - rows.end) as f32 * ROW_STRIDE,
+        )));
+
+        widget::scrollable(column)
+            .on_scroll(Message::PageScroll)
+            .into()
+    }
+
+    pub(crate) fn page_scroll(&mut self, viewport: scrollable::Viewport) {
+        self.scroll_offset = viewport.absolute_offset().y;
+        self.viewport_height = viewport.bounds().height;
+        self.request_visible_avatars();
+    }
+}
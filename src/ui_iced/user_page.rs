@@ -1,4 +1,4 @@
-use super::{app::Message, song_list::SongList};
+use super::{app::Message, song::format_count, song_list::SongList, user_grid::UserGrid};
 use crate::model;
 use iced::widget;
 
@@ -6,50 +6,272 @@ use iced::widget::text;
 use iced::Element;
 use std::sync::Arc;
 
+const DIM_COLOR: iced::Color = iced::Color::from_rgb(0.6, 0.6, 0.6);
+const AVATAR_SIZE: f32 = 200.0;
+
+type UserList = Option<Result<UserGrid, String>>;
+type Songs = Option<Result<SongList, String>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserTab {
+    Likes,
+    Tracks,
+    Reposts,
+    Followers,
+    Followings,
+}
+
+/// What needs loading the first time a tab is opened
+pub enum TabLoad {
+    Songs(model::Id, UserTab),
+    Users(model::UserListKey),
+}
+
 pub struct UserPage {
     user: Arc<model::User>,
     store: Arc<model::Store>,
-    pub song_list: Option<SongList>,
+    /// Bigger than the avatar that comes with user, which is for lists
+    avatar: model::Eventually<widget::image::Handle>,
+    tab: UserTab,
+    /// None until the tab is first opened and they load
+    likes: Songs,
+    tracks: Songs,
+    reposts: Songs,
+    followers: UserList,
+    followings: UserList,
 }
 
 impl UserPage {
     pub fn new(user: Arc<model::User>, store: &Arc<model::Store>) -> Self {
+        let avatar = store.lazy_image(user.avatar_url.as_deref(), model::ImageSize::Hero);
+        avatar.request();
+
         Self {
             user,
             store: store.clone(),
-            song_list: None,
+            avatar,
+            tab: UserTab::Likes,
+            likes: None,
+            tracks: None,
+            reposts: None,
+            followers: None,
+            followings: None,
+        }
+    }
+
+    /// Returns what needs loading for this tab, if anything
+    pub fn select_tab(&mut self, tab: UserTab) -> Option<TabLoad> {
+        self.tab = tab;
+
+        let id = self.user.id;
+        match tab {
+            UserTab::Likes | UserTab::Tracks | UserTab::Reposts if self.songs(tab).is_none() => {
+                Some(TabLoad::Songs(id, tab))
+            }
+            UserTab::Followers if self.followers.is_none() => {
+                Some(TabLoad::Users(model::UserListKey::Followers(id)))
+            }
+            UserTab::Followings if self.followings.is_none() => {
+                Some(TabLoad::Users(model::UserListKey::Followings(id)))
+            }
+            _ => None,
+        }
+    }
+
+    fn songs(&self, tab: UserTab) -> &Songs {
+        match tab {
+            UserTab::Tracks => &self.tracks,
+            UserTab::Reposts => &self.reposts,
+            _ => &self.likes,
+        }
+    }
+
+    fn songs_mut(&mut self, tab: UserTab) -> &mut Songs {
+        match tab {
+            UserTab::Tracks => &mut self.tracks,
+            UserTab::Reposts => &mut self.reposts,
+            _ => &mut self.likes,
+        }
+    }
+
+    pub fn songs_resolved(
+        &mut self,
+        id: model::Id,
+        tab: UserTab,
+        songs: Result<Arc<model::Playlist>, String>,
+    ) {
+        if id == self.user.id {
+            *self.songs_mut(tab) = Some(songs.map(SongList::new));
+        }
+    }
+
+    /// Song list of the current tab, if it is a tab of songs that has loaded
+    pub fn song_list(&self) -> Option<&SongList> {
+        match self.tab {
+            UserTab::Followers | UserTab::Followings => None,
+            tab => self.songs(tab).as_ref()?.as_ref().ok(),
+        }
+    }
+
+    pub fn song_list_mut(&mut self) -> Option<&mut SongList> {
+        match self.tab {
+            UserTab::Followers | UserTab::Followings => None,
+            tab => self.songs_mut(tab).as_mut()?.as_mut().ok(),
+        }
+    }
+
+    pub fn user_list_resolved(
+        &mut self,
+        key: model::UserListKey,
+        users: Result<Arc<Vec<Arc<model::User>>>, String>,
+    ) {
+        let users = users.map(UserGrid::new);
+
+        match key {
+            model::UserListKey::Followers(id) if id == self.user.id => self.followers = Some(users),
+            model::UserListKey::Followings(id) if id == self.user.id => {
+                self.followings = Some(users)
+            }
+            _ => {}
+        }
+    }
+
+    fn tabs(&self) -> Element<Message> {
+        let tab = |label: &str, target: UserTab| {
+            let button = widget::button(text(label));
+            if self.tab == target {
+                button
+            } else {
+                button
+                    .style(iced::theme::Button::Text)
+                    .on_press(Message::UserTabChanged(target))
+            }
+        };
+
+        widget::row!(
+            tab("Likes", UserTab::Likes),
+            tab("Tracks", UserTab::Tracks),
+            tab("Reposts", UserTab::Reposts),
+            tab("Followers", UserTab::Followers),
+            tab("Following", UserTab::Followings),
+        )
+        .spacing(10)
+        .into()
+    }
+
+    fn song_list<'a>(songs: &'a Songs, empty: &str) -> Element<'a, Message> {
+        match songs {
+            None => text("Loading...").into(),
+            Some(Err(err)) => text(format!("Could not load songs: {err}")).into(),
+            Some(Ok(songs)) if songs.models().next().is_none() => text(empty).into(),
+            Some(Ok(songs)) => songs.view(),
+        }
+    }
+
+    fn user_list<'a>(users: &'a UserList, empty: &str) -> Element<'a, Message> {
+        match users {
+            // NOTE(emily): Pending requests are only made when the tab is opened
+            None => text("Loading...").into(),
+            Some(Err(err)) => text(format!("Could not load users: {err}")).into(),
+            Some(Ok(users)) if users.is_empty() => text(empty).into(),
+            Some(Ok(users)) => users.view(),
         }
     }
 
     pub fn view(&self) -> Element<Message> {
         let mut column = widget::column!().spacing(20);
 
-        let user_avatar: Element<Message> = if let Some(avatar) = &self.user.avatar {
+        let user_avatar: Element<Message> = if let Some(avatar) = self.avatar.maybe() {
             iced::widget::image::Image::new(avatar.as_ref().clone())
-                .width(iced::Length::Fixed(100.0))
+                .width(iced::Length::Fixed(AVATAR_SIZE))
                 .into()
         } else {
-            text("").into()
+            widget::Space::with_width(iced::Length::Fixed(AVATAR_SIZE)).into()
         };
 
         column = column.push(
             widget::row!()
                 .push(user_avatar)
-                .push(text(format!("{}", self.user.username))),
+                .push(self.header())
+                .spacing(20),
         );
 
-        if let Some(page) = &self.song_list {
-            column = column.push(page.view());
+        column = column.push(self.tabs());
+
+        match self.tab {
+            UserTab::Likes => column = column.push(Self::song_list(&self.likes, "No likes")),
+            UserTab::Tracks => column = column.push(Self::song_list(&self.tracks, "No tracks")),
+            UserTab::Reposts => column = column.push(Self::song_list(&self.reposts, "No reposts")),
+            UserTab::Followers => {
+                column = column.push(Self::user_list(&self.followers, "No followers"))
+            }
+            UserTab::Followings => {
+                column = column.push(Self::user_list(&self.followings, "Not following anyone"))
+            }
         }
 
         column.into()
     }
 
-    pub fn update_songs(&mut self, songs: Arc<model::Playlist>) {
-        self.song_list = Some(SongList::new(songs))
+    fn header(&self) -> Element<Message> {
+        let user = &self.user;
+
+        let name = if user.verified {
+            format!("{} \u{2713}", user.username)
+        } else {
+            user.username.clone()
+        };
+
+        let mut column = widget::column!(text(name).size(40)).spacing(10);
+
+        let location = match (&user.city, &user.country) {
+            (Some(city), Some(country)) => Some(format!("{city}, {country}")),
+            (Some(place), None) | (None, Some(place)) => Some(place.clone()),
+            (None, None) => None,
+        };
+        if let Some(location) = location {
+            column = column.push(text(location).style(DIM_COLOR));
+        }
+
+        column = column.push(
+            text(format!(
+                "{} followers \u{b7} {} following \u{b7} {} tracks \u{b7} {} likes",
+                format_count(user.followers),
+                format_count(user.followings),
+                format_count(user.track_count),
+                format_count(user.likes),
+            ))
+            .style(DIM_COLOR),
+        );
+
+        if let Some(description) = &user.description {
+            column = column.push(text(description).size(14));
+        }
+
+        column.into()
     }
 
-    pub(crate) fn page_scroll(&mut self, amount: f32) {
-        self.song_list.as_mut().map(|l| l.page_scroll(amount));
+    /// Swap in the fully looked up user, the one that we opened the page with
+    /// usually came attached to a song and is missing most of the details
+    pub fn user_resolved(&mut self, user: Arc<model::User>) {
+        if user.id == self.user.id {
+            self.user = user;
+        }
+    }
+
+    fn user_grid_mut(&mut self) -> Option<&mut UserGrid> {
+        match self.tab {
+            UserTab::Followers => self.followers.as_mut()?.as_mut().ok(),
+            UserTab::Followings => self.followings.as_mut()?.as_mut().ok(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn page_scroll(&mut self, viewport: widget::scrollable::Viewport) {
+        if let Some(grid) = self.user_grid_mut() {
+            grid.page_scroll(viewport);
+        } else if let Some(list) = self.song_list_mut() {
+            list.page_scroll(viewport);
+        }
     }
 }
@@ -0,0 +1,176 @@
+use iced::mouse;
+use iced::widget::canvas::{self, event, Canvas, Frame, Geometry, Path};
+use iced::{Color, Element, Length, Point, Rectangle, Renderer, Size, Theme};
+
+use crate::model;
+
+use super::app::Message;
+
+const PLAYED_COLOR: Color = Color::from_rgb(1.0, 0.55, 0.0);
+const BUFFERED_COLOR: Color = Color::from_rgb(0.6, 0.6, 0.6);
+const REMAINING_COLOR: Color = Color::from_rgb(0.3, 0.3, 0.3);
+const MARKER_COLOR: Color = Color::from_rgb(0.9, 0.9, 0.9);
+
+const BAR_WIDTH: f32 = 2.0;
+const BAR_GAP: f32 = 1.0;
+/// Height of the bars when we dont have a waveform (yet)
+const FLAT_HEIGHT: f32 = 0.3;
+/// Comment markers sit above the bars
+const MARKER_WIDTH: f32 = 2.0;
+const MARKER_HEIGHT: f32 = 4.0;
+
+/// Progress bar that shows the loudness of the song. Clicking on it seeks there.
+pub struct Waveform<'a> {
+    waveform: Option<&'a model::Waveform>,
+    comments: &'a [model::Comment],
+    /// All in seconds
+    position: f32,
+    buffered: f32,
+    total: f32,
+}
+
+impl<'a> Waveform<'a> {
+    pub fn new(
+        waveform: Option<&'a model::Waveform>,
+        position: f32,
+        buffered: f32,
+        total: f32,
+    ) -> Self {
+        Self {
+            waveform,
+            comments: &[],
+            position,
+            buffered,
+            total,
+        }
+    }
+
+    /// Mark where each of these comments were left
+    pub fn comments(mut self, comments: &'a [model::Comment]) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    pub fn view(self, height: f32) -> Element<'a, Message> {
+        Canvas::new(self)
+            .width(Length::Fill)
+            .height(Length::Fixed(height))
+            .into()
+    }
+
+    /// How far along the song (0.0 to 1.0) secs is
+    fn fraction(&self, secs: f32) -> f32 {
+        if self.total > 0.0 {
+            (secs / self.total).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Height (0.0 to 1.0) of the bar at fraction along the song
+    fn amplitude(&self, fraction: f32) -> f32 {
+        match self.waveform {
+            Some(waveform) if !waveform.samples.is_empty() => {
+                let i = (fraction * waveform.samples.len() as f32) as usize;
+                waveform.samples[i.min(waveform.samples.len() - 1)]
+            }
+            _ => FLAT_HEIGHT,
+        }
+    }
+}
+
+impl<'a> canvas::Program<Message> for Waveform<'a> {
+    type State = ();
+
+    fn update(
+        &self,
+        _state: &mut Self::State,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (event::Status, Option<Message>) {
+        let canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event else {
+            return (event::Status::Ignored, None);
+        };
+
+        let Some(position) = cursor.position_in(bounds) else {
+            return (event::Status::Ignored, None);
+        };
+
+        if self.total <= 0.0 {
+            return (event::Status::Ignored, None);
+        }
+
+        let fraction = (position.x / bounds.width).clamp(0.0, 1.0);
+        (
+            event::Status::Captured,
+            Some(Message::Seek(fraction * self.total)),
+        )
+    }
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+
+        let played = self.fraction(self.position);
+        let buffered = self.fraction(self.buffered);
+
+        // NOTE(emily): Leave room for the markers even if there arent any, so
+        // that the bars dont jump around when the comments load
+        let bar_space = bounds.height - MARKER_HEIGHT - 1.0;
+
+        let mut x = 0.0;
+        while x < bounds.width {
+            let fraction = x / bounds.width;
+            let height = (self.amplitude(fraction) * bar_space).max(1.0);
+
+            let color = if fraction < played {
+                PLAYED_COLOR
+            } else if fraction < buffered {
+                BUFFERED_COLOR
+            } else {
+                REMAINING_COLOR
+            };
+
+            // NOTE(emily): Bars grow out of the bottom, like they do on SoundCloud
+            frame.fill(
+                &Path::rectangle(
+                    Point::new(x, bounds.height - height),
+                    Size::new(BAR_WIDTH, height),
+                ),
+                color,
+            );
+
+            x += BAR_WIDTH + BAR_GAP;
+        }
+
+        for timestamp in self.comments.iter().filter_map(|c| c.timestamp) {
+            let x = self.fraction(timestamp as f32 / 1000.0) * bounds.width;
+            frame.fill(
+                &Path::rectangle(Point::new(x, 0.0), Size::new(MARKER_WIDTH, MARKER_HEIGHT)),
+                MARKER_COLOR,
+            );
+        }
+
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        _state: &Self::State,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        if cursor.is_over(bounds) {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}